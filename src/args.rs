@@ -47,6 +47,34 @@ pub(crate) struct Args {
     /// Whether to use safe connection to socket (yes/no)
     #[arg(long, default_value = NO)]
     socsafe: String,
+
+    /// The file of configuration sentences to run at startup
+    #[arg(long)]
+    config: Option<String>,
+
+    /// The file to save configuration sentences, used by 'save' without path
+    #[arg(long)]
+    save: Option<String>,
+
+    /// Whether to save configuration sentences after every change (yes/no)
+    #[arg(long, default_value = NO)]
+    autosave: String,
+
+    /// The socket to serve metrics for Prometheus at '/metrics'
+    #[arg(long)]
+    metrics: Option<String>,
+
+    /// The file of tokens to authenticate on the socket, one 'name:role:token' per line
+    #[arg(long)]
+    tokens: Option<String>,
+
+    /// The file to append audit records of configuration sentences, one JSON per line
+    #[arg(long)]
+    audit: Option<String>,
+
+    /// The syslog socket to send audit records to over UDP, like '127.0.0.1:514'
+    #[arg(long)]
+    syslog: Option<String>,
 }
 
 impl Args {
@@ -62,6 +90,34 @@ impl Args {
         NO != self.socsafe
     }
 
+    pub(crate) fn config(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    pub(crate) fn save(&self) -> Option<String> {
+        self.save.clone()
+    }
+
+    pub(crate) fn metrics(&self) -> Option<String> {
+        self.metrics.clone()
+    }
+
+    pub(crate) fn tokens(&self) -> Option<&str> {
+        self.tokens.as_deref()
+    }
+
+    pub(crate) fn audit(&self) -> Option<&str> {
+        self.audit.as_deref()
+    }
+
+    pub(crate) fn syslog(&self) -> Option<&str> {
+        self.syslog.as_deref()
+    }
+
+    pub(crate) fn is_autosave(&self) -> bool {
+        YES == self.autosave
+    }
+
     pub(crate) fn ipscope(&self) -> Vec<IpAddr> {
         self.ipscope
             .split(',')
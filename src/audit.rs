@@ -0,0 +1,108 @@
+use log::error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::OnceLock;
+use tokio::sync::mpsc;
+
+///priority of syslog record, facility "log audit" and severity "informational".
+const PRIORITY: u8 = 13 * 8 + 6;
+
+///records which wait for the writer, a sentence waits if it's full.
+const AUDIT_CAPACITY: usize = 1000;
+
+///records are sent to the writer thread, files and sockets aren't touched by async tasks.
+static AUDIT: OnceLock<mpsc::Sender<String>> = OnceLock::new();
+
+///records are appended to file, and forwarded to syslog if there is an address.
+struct Audit {
+    file: Option<File>,
+    syslog: Option<(UdpSocket, SocketAddr)>,
+}
+
+impl Audit {
+    //it ends when all senders are dropped.
+    fn write_loop(mut self, mut receiver: mpsc::Receiver<String>) {
+        while let Some(line) = receiver.blocking_recv() {
+            if let Some(f) = &mut self.file {
+                let _ = writeln!(f, "{line}").map_err(|e| error!("audit: {e}"));
+            }
+            if let Some((socket, addr)) = &self.syslog {
+                let s = to_syslog(&line, std::process::id());
+                let _ = socket
+                    .send_to(s.as_bytes(), addr)
+                    .map_err(|e| error!("syslog[{addr}]: {e}"));
+            }
+        }
+    }
+}
+
+fn open(path: &str) -> Option<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| error!("audit[{path}]: {e}"))
+        .ok()
+}
+
+fn syslog(addr: &str) -> Option<(UdpSocket, SocketAddr)> {
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(|e| error!("syslog[{addr}]: {e}"))
+        .ok()?
+        .next()?;
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    UdpSocket::bind(local)
+        .map(|o| (o, addr))
+        .map_err(|e| error!("syslog[{addr}]: {e}"))
+        .ok()
+}
+
+///audit to file "path" and syslog "addr", return false if either can't be used.
+///records are written by a thread of their own.
+pub(crate) fn set_up(path: Option<&str>, addr: Option<&str>) -> bool {
+    if path.is_none() && addr.is_none() {
+        return true;
+    }
+    let file = match path.map(open) {
+        Some(None) => return false,
+        o => o.flatten(),
+    };
+    let syslog = match addr.map(syslog) {
+        Some(None) => return false,
+        o => o.flatten(),
+    };
+    let (sender, receiver) = mpsc::channel(AUDIT_CAPACITY);
+    if AUDIT.set(sender).is_err() {
+        return false;
+    }
+    let a = Audit { file, syslog };
+    std::thread::spawn(move || a.write_loop(receiver));
+    true
+}
+
+pub(crate) fn is_enabled() -> bool {
+    AUDIT.get().is_some()
+}
+
+///append a record as one line, and send it to syslog as RFC 5424 message.
+pub(crate) async fn record(line: String) {
+    if let Some(s) = AUDIT.get() {
+        let _ = s.send(line).await.map_err(|e| error!("audit: {e}"));
+    }
+}
+
+//like "<110>1 - - netproxy 100 audit - {...}", time and host are filled by syslog.
+fn to_syslog(line: &str, pid: u32) -> String {
+    format!("<{PRIORITY}>1 - - netproxy {pid} audit - {line}")
+}
+
+#[test]
+fn test() {
+    assert_eq!(to_syslog("{}", 100), "<110>1 - - netproxy 100 audit - {}");
+}
@@ -0,0 +1,122 @@
+use getset::{CopyGetters, Getters};
+use log::error;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+const ADMIN: &str = "admin";
+const READ: &str = "read";
+
+///tokens and their users, control socket requires authentication if they are loaded.
+static TOKENS: OnceLock<Vec<(String, User)>> = OnceLock::new();
+
+///"Read" can only show state, "Admin" can do everything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Role {
+    Read,
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => READ,
+            Self::Admin => ADMIN,
+        })
+    }
+}
+
+///who a connection is authenticated as.
+#[derive(Clone, Debug, CopyGetters, Getters)]
+pub(crate) struct User {
+    #[getset(get = "pub(crate)")]
+    name: String,
+    #[getset(get_copy = "pub(crate)")]
+    role: Role,
+}
+
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({})", self.name, self.role)
+    }
+}
+
+///load tokens from file, one "name:role:token" per line, role is "admin" or "read".
+///a line starting with '#' is comment.
+pub(crate) fn load_tokens(path: &str) -> bool {
+    let s = if let Ok(o) = fs::read_to_string(path).map_err(|e| error!("tokens[{path}]: {e}")) {
+        o
+    } else {
+        return false;
+    };
+    let mut v = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut iter = line.splitn(3, ':');
+        let (name, role, token) = match (iter.next(), iter.next(), iter.next()) {
+            (Some(n), Some(r), Some(t)) if !n.is_empty() && !t.is_empty() => (n, r, t),
+            _ => {
+                error!("tokens[{path}] line {}: not 'name:role:token'", i + 1);
+                return false;
+            }
+        };
+        let role = match role {
+            ADMIN => Role::Admin,
+            READ => Role::Read,
+            _ => {
+                error!("tokens[{path}] line {}: role {role}", i + 1);
+                return false;
+            }
+        };
+        let name = name.to_string();
+        v.push((token.to_string(), User { name, role }));
+    }
+    //nobody could authenticate without a token.
+    if v.is_empty() {
+        error!("tokens[{path}]: no token");
+        return false;
+    }
+    TOKENS.set(v).is_ok()
+}
+
+///true if tokens are loaded.
+pub(crate) fn is_required() -> bool {
+    TOKENS.get().is_some()
+}
+
+///user of "token", every token is compared in constant time.
+pub(crate) fn identify(token: &str) -> Option<User> {
+    let mut o = None;
+    for (t, u) in TOKENS.get()? {
+        if equal(t.as_bytes(), token.as_bytes()) {
+            o = Some(u.clone());
+        }
+    }
+    o
+}
+
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |n, (x, y)| n | (x ^ y)) == 0
+}
+
+#[test]
+fn test() {
+    assert!(equal(b"abc", b"abc"));
+    assert!(!equal(b"abc", b"abd"));
+    assert!(!equal(b"abc", b"ab"));
+    assert_eq!(
+        User {
+            name: "a".to_string(),
+            role: Role::Read
+        }
+        .to_string(),
+        "a(read)"
+    );
+    let path = std::env::temp_dir().join("netproxy_tokens_test");
+    fs::write(&path, "# no token\n\n").unwrap();
+    assert!(!load_tokens(&path.to_string_lossy()));
+    let _ = fs::remove_file(&path);
+}
@@ -3,9 +3,11 @@ use std::fmt::Debug;
 use tokio::io::{
     self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
 };
-use tokio::net::TcpStream;
-use tokio_native_tls::native_tls::TlsConnector as NativeTlsConnector;
-use tokio_native_tls::TlsConnector;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_native_tls::native_tls::{
+    Identity, TlsAcceptor as NativeTlsAcceptor, TlsConnector as NativeTlsConnector,
+};
+use tokio_native_tls::{TlsAcceptor, TlsConnector};
 
 const END_FLAG: &str = ":!";
 
@@ -15,7 +17,9 @@ const NO: &str = "no";
 async fn main() {
     let args = Args::parse();
 
-    if args.is_safe() {
+    if let Some(p) = &args.p12 {
+        serve(&args, p).await;
+    } else if args.is_safe() {
         connsafe(&args).await;
     } else {
         conn(&args).await;
@@ -32,7 +36,7 @@ async fn connsafe(args: &Args) -> bool {
     }
     match c.connect(s.as_str(), socket).await {
         Ok(socket) => {
-            input(socket).await;
+            input(socket, args).await;
             return true;
         }
         Err(e) => {
@@ -44,16 +48,38 @@ async fn connsafe(args: &Args) -> bool {
 
 async fn conn(args: &Args) -> bool {
     let socket = TcpStream::connect(&args.to).await.unwrap();
-    input(socket).await;
+    input(socket, args).await;
     true
 }
 
-async fn input<T>(mut socket: T)
+//a message is a big-endian u32 length followed by data.
+async fn send<T>(socket: &mut T, data: &[u8]) -> String
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    socket.write_u32(data.len() as u32).await.unwrap();
+    socket.write_all(data).await.unwrap();
+    let n = socket.read_u32().await.unwrap();
+    let mut rsp = vec![0; n as usize];
+    socket.read_exact(&mut rsp).await.unwrap();
+    String::from_utf8_lossy(&rsp).to_string()
+}
+
+async fn input<T>(mut socket: T, args: &Args)
 where
     T: AsyncRead + AsyncWrite + AsyncWriteExt + Unpin + Debug,
 {
-    println!("{:?}", socket);
     let mut out = io::stdout();
+    //sentences of file are sent in one message.
+    if let Some(p) = &args.batch {
+        let data = std::fs::read(p).unwrap();
+        let rsp = send(&mut socket, &data).await;
+        out.write_all(rsp.as_bytes()).await.unwrap();
+        out.write_all(b"\n").await.unwrap();
+        out.flush().await.unwrap();
+        return;
+    }
+    println!("{:?}", socket);
     out.write_all(b"Please input configuration.\n")
         .await
         .unwrap();
@@ -73,28 +99,81 @@ where
             out.write_all(b"cfgtool closed.\n").await.unwrap();
             return;
         }
-        socket.write_all(ibuf.as_bytes()).await.unwrap();
-
-        let mut tbuf = [0; 1024];
-        let n = socket.read(&mut tbuf).await.unwrap();
-        let rsp = String::from_utf8(tbuf[0..n].to_vec()).unwrap();
+        let rsp = send(&mut socket, ibuf.as_bytes()).await;
         out.write_all(rsp.as_bytes()).await.unwrap();
         out.write_all(b"\n").await.unwrap();
         out.flush().await.unwrap();
     }
 }
 
+//serve a PKCS#12 file for 'certificate s' sentence.
+//every connection receives one frame, a big-endian u32 length followed by data.
+async fn serve(args: &Args, path: &str) {
+    let data = std::fs::read(path).unwrap();
+    let acceptor = if args.is_safe() {
+        let i = Identity::from_pkcs12(&data, &args.pwd).unwrap();
+        Some(TlsAcceptor::from(NativeTlsAcceptor::new(i).unwrap()))
+    } else {
+        None
+    };
+    let mut frame = (data.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(&data);
+
+    let listener = TcpListener::bind(&args.to).await.unwrap();
+    println!("serve {} on {:?}", path, listener.local_addr().unwrap());
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(o) => o,
+            Err(e) => {
+                println!("{:?}", e);
+                continue;
+            }
+        };
+        println!("send to {}", addr);
+        if let Some(a) = &acceptor {
+            match a.accept(socket).await {
+                Ok(socket) => send_frame(socket, &frame).await,
+                Err(e) => println!("{:?}", e),
+            }
+        } else {
+            send_frame(socket, &frame).await;
+        }
+    }
+}
+
+async fn send_frame<T>(mut socket: T, frame: &[u8])
+where
+    T: AsyncWrite + AsyncWriteExt + Unpin,
+{
+    if let Err(e) = socket.write_all(frame).await {
+        println!("{:?}", e);
+    }
+    let _ = socket.shutdown().await;
+}
+
 /// Arguments
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// The socket to send configuration
+    /// The socket to send configuration, or to serve PKCS#12 file on
     #[arg(short, long)]
     pub to: String,
 
+    /// Serve the PKCS#12 file for 'certificate s' sentence instead of sending configuration
+    #[arg(long)]
+    p12: Option<String>,
+
+    /// The password of PKCS#12 file, to serve with safe connection
+    #[arg(long, default_value = "")]
+    pwd: String,
+
     /// Whether to use safe connection to socket (yes/no)
     #[arg(long, default_value = NO)]
     socsafe: String,
+
+    /// Send sentences of the file in one message, then exit
+    #[arg(long)]
+    batch: Option<String>,
 }
 
 impl Args {
@@ -1,24 +1,35 @@
 use crate::args::*;
+use crate::audit;
+use crate::auth::{self, Role, User};
 use crate::core::*;
+use crate::metrics;
 use crate::route::{self, *};
 use crate::state::{self, *};
 use crate::visit::{self, *};
 use async_trait::async_trait;
 use std::fmt::Debug;
+use std::fs;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::{OnceCell, RwLock};
 
-const HTTP: &str = "http";
-const HTTP_PT: &str = "http_pt";
-const TCP: &str = "tcp";
-const TLS: &str = "tls";
-const UDP: &str = "udp";
 const CERTIFICATE: &str = "certificate";
 const STATE: &str = "state";
 const SHUTDOWN: &str = "shutdown";
+const SAVE: &str = "save";
+const UPDATE: &str = "update";
+const FORMAT: &str = "format";
+const JSON: &str = "json";
+const TEXT: &str = "text";
+const AUTH: &str = "auth";
 
-const OUTCOMES: [&[u8]; 8] = [
+///failed "auth" sentences which close a connection.
+const AUTH_ATTEMPTS: u32 = 3;
+
+//certificate password like "env:NAME" is read from environment variable.
+const ENV: &str = "env:";
+
+const OUTCOMES: [&[u8]; 15] = [
     b"ok",
     b"configuration sentences error",
     b"protocol error",
@@ -27,6 +38,13 @@ const OUTCOMES: [&[u8]; 8] = [
     b"certificate error",
     b"server is starting up...",
     b"shutdown error",
+    b"save error",
+    b"option error",
+    b"auth error",
+    b"authentication required",
+    b"permission denied",
+    b"update error",
+    b"route with pools can't be updated",
 ];
 
 static mut SAFE: bool = false;
@@ -44,6 +62,8 @@ fn to_protoc(s: &str) -> Option<Protoc> {
         TCP => Some(Protoc::TCP),
         TLS => Some(Protoc::TLS),
         UDP => Some(Protoc::UDP),
+        SOCKS5 => Some(Protoc::SOCKS5),
+        SNI => Some(Protoc::SNI),
         _ => None,
     }
 }
@@ -68,13 +88,16 @@ enum RuleType {
     Route(RouteInfo),
     Visit(VisitInfo),
     CertificateF(String, String),
-    CertificateS(String, String),
+    CertificateS(String, String, bool),
     State(Option<SocketAddr>),
     Shutdown(SocketAddr),
+    Save(Option<String>),
+    Update(SocketAddr, Vec<String>, Vec<usize>),
 }
 
 impl TryFrom<&str> for RuleType {
-    type Error = usize;
+    ///index of OUTCOMES, and what is wrong.
+    type Error = (usize, String);
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         trace!("configuration check");
@@ -98,10 +121,11 @@ impl TryFrom<&str> for RuleType {
                                         return Ok(Self::CertificateS(
                                             c.to_string(),
                                             d.to_string(),
+                                            iter.next() == Some(TLS),
                                         ));
                                     }
                                     _ => {
-                                        return Err(5);
+                                        return Err((5, b.to_string()));
                                     }
                                 }
                             }
@@ -120,6 +144,39 @@ impl TryFrom<&str> for RuleType {
                         return Ok(Self::Shutdown(b));
                     }
                 }
+                SAVE => {
+                    trace!("save configuration");
+                    return Ok(Self::Save(iter.next().map(|s| s.to_string())));
+                }
+                UPDATE => {
+                    if let Some(b) = iter.next() {
+                        let b = if let Ok(o) = b.parse::<SocketAddr>() {
+                            o
+                        } else {
+                            return Err((3, b.to_string()));
+                        };
+                        if let Some(c) = iter.next() {
+                            trace!("update configuration");
+                            //proportion is digits only, nothing follows it.
+                            let p = iter.next();
+                            if let Some(o) = p.filter(|o| !is_proportion(o)) {
+                                return Err((13, o.to_string()));
+                            }
+                            if let Some(o) = iter.next() {
+                                return Err((13, o.to_string()));
+                            }
+                            let (ra, mut proportion) = some_addr_proportion(c, p);
+                            if let Some(o) = ra.iter().find(|o| !is_target(o)) {
+                                return Err((3, o.clone()));
+                            }
+                            if let Some(o) = duplicate(&ra) {
+                                return Err((3, o.clone()));
+                            }
+                            divide(&mut proportion);
+                            return Ok(Self::Update(b, ra, proportion));
+                        }
+                    }
+                }
                 _ => {
                     //accept protocol and route target protocol, split by '-', if only one,the other is the same.
                     let (p1, p2) = a.split_once('-').unwrap_or((a, a));
@@ -127,25 +184,51 @@ impl TryFrom<&str> for RuleType {
                         if let Some(p2) = to_protoc(p2) {
                             if let Some(b) = iter.next() {
                                 if b.parse::<SocketAddr>().is_err() {
-                                    return Err(3);
+                                    return Err((3, b.to_string()));
                                 }
-                                if let Some(c) = iter.next() {
+                                let c = iter.next();
+                                if let Some(c) = c.filter(|c| !c.contains('=')) {
                                     trace!("transfer configuration");
                                     if c.is_empty() {
-                                        return Err(3);
+                                        return Err((3, c.to_string()));
+                                    }
+                                    //options like "key=value" follow proportion.
+                                    let mut p = None;
+                                    let mut options = Vec::new();
+                                    for o in iter {
+                                        if let Some(kv) = o.split_once('=') {
+                                            options.push(kv);
+                                        } else if p.is_none() && options.is_empty() {
+                                            p = Some(o);
+                                        } else {
+                                            return Err((9, o.to_string()));
+                                        }
+                                    }
+                                    let (ra, mut proportion) = some_addr_proportion(c, p);
+                                    if let Some(o) = duplicate(&ra) {
+                                        return Err((3, o.clone()));
                                     }
-                                    let (ra, mut proportion) = some_addr_proportion(c, iter.next());
                                     divide(&mut proportion);
-                                    return Ok(Self::Route(RouteInfo::new(
-                                        p1,
-                                        b.to_string(),
-                                        p2,
-                                        ra,
-                                        proportion,
-                                    )));
+                                    let mut r =
+                                        RouteInfo::new(p1, b.to_string(), p2, ra, proportion);
+                                    for (k, v) in options {
+                                        if !r.set_option(k, v) {
+                                            return Err((9, format!("{k}={v}")));
+                                        }
+                                    }
+                                    return Ok(Self::Route(r));
                                 } else {
                                     trace!("visit configuration");
-                                    return Ok(Self::Visit(VisitInfo::new(p1, b.to_string(), p2)));
+                                    let mut v = VisitInfo::new(p1, b.to_string(), p2);
+                                    for o in c.into_iter().chain(iter) {
+                                        if let Some((k, o)) = o.split_once('=') {
+                                            if v.set_option(k, o) {
+                                                continue;
+                                            }
+                                        }
+                                        return Err((9, o.to_string()));
+                                    }
+                                    return Ok(Self::Visit(v));
                                 }
                             }
                         }
@@ -153,90 +236,207 @@ impl TryFrom<&str> for RuleType {
                 }
             }
         }
-        Err(1)
+        Err((1, s.trim().to_string()))
     }
 }
 
 macro_rules! check_safe {
-    ($a:ident) => {
+    () => {
         unsafe {
             if SAFE {
-                $a.extend_from_slice(OUTCOMES[4]);
-                return;
+                return Reply::new(4);
             }
         }
     };
 }
 
+///outcome of a sentence, "code" is index of OUTCOMES and "detail" tells what is wrong.
+///state is replied with its text and JSON.
+struct Reply {
+    code: usize,
+    detail: String,
+    state: Option<(String, String)>,
+}
+
+impl Reply {
+    fn new(code: usize) -> Self {
+        Self::error(code, String::new())
+    }
+
+    fn error(code: usize, detail: String) -> Self {
+        Self {
+            code,
+            detail,
+            state: None,
+        }
+    }
+
+    fn state(text: String, json: String) -> Self {
+        Self {
+            code: 0,
+            detail: String::new(),
+            state: Some((text, json)),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.code == 0 || self.code == 6
+    }
+
+    ///text like "ok", or JSON like '{"code":0,"message":"ok","detail":"","data":null}'.
+    fn write(&self, rsp: &mut Vec<u8>, json: bool) {
+        if !json {
+            match &self.state {
+                Some((text, _)) => rsp.extend_from_slice(text.as_bytes()),
+                None => rsp.extend_from_slice(OUTCOMES[self.code]),
+            }
+            return;
+        }
+        let data = self.state.as_ref().map(|o| o.1.as_str()).unwrap_or("null");
+        let s = format!(
+            "{{\"code\":{},\"message\":{},\"detail\":{},\"data\":{}}}",
+            self.code,
+            json_str(&into_str(OUTCOMES[self.code])),
+            json_str(&self.detail),
+            data
+        );
+        rsp.extend_from_slice(s.as_bytes());
+    }
+}
+
 impl RuleType {
-    async fn handle(self, rsp: &mut Vec<u8>) {
+    ///server which the sentence starts or shuts down.
+    fn server(&self) -> Option<String> {
+        match self {
+            Self::Route(o) => Some(o.server_addr().clone()),
+            Self::Visit(o) => Some(o.server_addr().clone()),
+            Self::Shutdown(o) | Self::Update(o, ..) => Some(o.to_string()),
+            _ => None,
+        }
+    }
+
+    async fn handle(self) -> Reply {
         match self {
             Self::Route(o) => {
-                check_safe!(rsp);
+                check_safe!();
                 route::start_up(o);
-                rsp.extend_from_slice(OUTCOMES[6]);
+                Reply::new(6)
             }
             Self::Visit(o) => {
-                check_safe!(rsp);
+                check_safe!();
                 visit::start_up(o);
-                rsp.extend_from_slice(OUTCOMES[6]);
+                Reply::new(6)
             }
             Self::CertificateF(a, b) => {
-                if build_certificate_from_file(a, b).await {
+                if !build_certificate_from_file(a.clone(), password(&b)).await {
                     error!("certificate error");
-                    rsp.extend_from_slice(OUTCOMES[5]);
-                    return;
+                    return Reply::error(5, a);
                 }
+                let source = format!("{CERTIFICATE} f {a}");
+                let sentence = password_ref(&source, &b).map(|p| format!("{source} {p}"));
+                state::add_certificate(&source, sentence).await;
                 send_stop().await;
-                rsp.extend_from_slice(OUTCOMES[0]);
+                Reply::new(0)
             }
-            Self::CertificateS(a, b) => {
-                if build_certificate_from_socket(a, b).await {
+            Self::CertificateS(a, b, c) => {
+                if !build_certificate_from_socket(a.clone(), password(&b), c).await {
                     error!("certificate error");
-                    rsp.extend_from_slice(OUTCOMES[5]);
-                    return;
+                    return Reply::error(5, a);
                 }
+                let source = format!("{CERTIFICATE} s {a}");
+                let sentence = password_ref(&source, &b).map(|p| {
+                    if c {
+                        format!("{source} {p} {TLS}")
+                    } else {
+                        format!("{source} {p}")
+                    }
+                });
+                state::add_certificate(&source, sentence).await;
                 send_stop().await;
-                rsp.extend_from_slice(OUTCOMES[0]);
+                Reply::new(0)
             }
             Self::State(o) => {
-                check_safe!(rsp);
+                check_safe!();
                 if let Some(o) = &o {
-                    rsp.extend_from_slice(state::state_string(o).await.as_bytes());
+                    Reply::state(state::state_string(o).await, state::state_json(o).await)
                 } else {
-                    rsp.extend_from_slice(state::list().await.as_bytes());
+                    Reply::state(state::list().await, state::list_json().await)
                 }
             }
             Self::Shutdown(o) => {
-                check_safe!(rsp);
+                check_safe!();
                 if state::shutdown(&o).await {
-                    rsp.extend_from_slice(OUTCOMES[0]);
+                    Reply::new(0)
+                } else {
+                    Reply::error(7, o.to_string())
+                }
+            }
+            Self::Save(o) => {
+                check_safe!();
+                if state::save(o.as_deref()).await {
+                    Reply::new(0)
+                } else if let Some(s) = state::unsaved_certificate().await {
+                    Reply::error(8, s)
                 } else {
-                    rsp.extend_from_slice(OUTCOMES[7]);
+                    Reply::error(8, o.unwrap_or_default())
+                }
+            }
+            Self::Update(o, a, p) => {
+                check_safe!();
+                //only a route can be updated, its sentence is saved with new targets.
+                let mut r = match state::sentence(&o)
+                    .await
+                    .map(|s| Self::try_from(s.as_str()))
+                {
+                    Some(Ok(Self::Route(r))) => r,
+                    _ => return Reply::error(13, o.to_string()),
+                };
+                if r.has_pools() {
+                    return Reply::error(14, o.to_string());
+                }
+                r.set_targets(a.clone(), p.clone());
+                if state::update(&o, r.to_string(), ControlInfo::Update(a, p)).await {
+                    Reply::new(0)
+                } else {
+                    Reply::error(13, o.to_string())
                 }
             }
         }
     }
 }
 
-//target socket addr, one or several, where data transfer to, split by ','
-//proportion of data transfer to target, split by ':'. it's digit and correspondence with third. if it's not digit, replace with 0. if number is less than socket addrs, fill with 1. it can be omitted.
-fn some_addr_proportion(a: &str, p: Option<&str>) -> (Vec<String>, Vec<usize>) {
-    let addr: Vec<String> = a.split(',').map(|s| s.to_string()).collect();
-    let addr_len = addr.len();
-
-    let proportion: Vec<usize> = if let Some(p) = p {
-        let mut p: Vec<usize> = p.split(':').map(|s| s.parse().unwrap_or(0)).collect();
-        if p.len() < addr_len {
-            p.extend_from_slice(&[addr_len - p.len(); 1]);
-        } else if p.len() > addr_len {
-            p.truncate(addr_len);
+//sentence with certificate password replaced by "***", unless it's "env:NAME".
+fn redact(s: &str) -> String {
+    let v: Vec<&str> = s.split_whitespace().collect();
+    match v.as_slice() {
+        [CERTIFICATE, b, c, d, ..] if !d.starts_with(ENV) => {
+            let mut o = vec![CERTIFICATE, *b, *c, "***"];
+            o.extend_from_slice(&v[4..]);
+            o.join(" ")
         }
-        p
+        _ => v.join(" "),
+    }
+}
+
+//password like "env:NAME" is read from environment variable NAME.
+fn password(s: &str) -> String {
+    if let Some(name) = s.strip_prefix(ENV) {
+        std::env::var(name)
+            .inspect_err(|e| error!("password {name}: {e}"))
+            .unwrap_or_default()
     } else {
-        [addr_len; 1].to_vec()
-    };
-    (addr, proportion)
+        s.to_string()
+    }
+}
+
+//password which is saved, only "env:NAME" since it's never saved in clear text.
+fn password_ref<'a>(source: &str, s: &'a str) -> Option<&'a str> {
+    if s.starts_with(ENV) {
+        Some(s)
+    } else {
+        warn!("password of \"{source}\" isn't {ENV}NAME, configuration can't be saved");
+        None
+    }
 }
 
 //if specify use tls connection, the first configuration sentence must be certificate sentence, then server will restart.
@@ -250,16 +450,65 @@ pub(crate) fn build(args: Args) {
     let addr = args.socket();
     let is_tool = args.is_tool();
     let ipscope = args.ipscope();
+    let config = args.config();
+    state::set_save(args.save(), args.is_autosave());
+    if !audit::set_up(args.audit(), args.syslog()) {
+        error!("audit error end");
+        return;
+    }
+    if let Some(path) = args.tokens() {
+        if !auth::load_tokens(path) {
+            error!("tokens error end");
+            return;
+        }
+    }
+    if let Some(a) = args.metrics() {
+        metrics::start_up(a);
+    }
 
-    tokiort_block_on(async { start_up(addr, is_tool, &ipscope, false).await });
+    tokiort_block_on(async { start_up(addr, is_tool, &ipscope, false, config).await });
 
     if args.is_safe() {
         debug!("current server restart");
-        tokiort_block_on(async { start_up(addr, is_tool, &ipscope, true).await });
+        tokiort_block_on(async { start_up(addr, is_tool, &ipscope, true, None).await });
+    }
+}
+
+//run configuration sentences from file, one sentence per line, a line starting with '#' is comment.
+async fn load_config(path: &str) {
+    let s = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("config file[{path}]: {e}");
+            return;
+        }
+    };
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let reply = match RuleType::try_from(line) {
+            Ok(o) => o.handle().await,
+            Err((n, d)) => Reply::error(n, d),
+        };
+        let mut rsp = Vec::new();
+        reply.write(&mut rsp, false);
+        if reply.is_ok() {
+            info!("config[{path}] line {}: {}", i + 1, into_str(&rsp));
+        } else {
+            error!("config[{path}] line {}: {}", i + 1, into_str(&rsp));
+        }
     }
 }
 
-async fn start_up(addr: &str, is_tool: bool, ipscope: &Vec<IpAddr>, is_safe: bool) {
+async fn start_up(
+    addr: &str,
+    is_tool: bool,
+    ipscope: &Vec<IpAddr>,
+    is_safe: bool,
+    config: Option<&str>,
+) {
     let (mut server, a, mut b) = if let Some(server) = Server::new(addr).await {
         server
     } else {
@@ -279,18 +528,20 @@ async fn start_up(addr: &str, is_tool: bool, ipscope: &Vec<IpAddr>, is_safe: boo
 
     tokio::spawn(async move {
         while let Some(o) = b.recv().await {
-            match o {
-                StateInfo::Sum(velocity, date_time) => {
-                    if let Some(ss) = current().await.write().await.as_mut() {
-                        ss.set_velocity(velocity);
-                        *ss.date_time_mut() = date_time;
-                    }
+            if let StateInfo::Sum(velocity, date_time) = o {
+                if let Some(ss) = current().await.write().await.as_mut() {
+                    ss.set_velocity(velocity);
+                    *ss.date_time_mut() = date_time;
                 }
             }
         }
         trace!("current state end");
     });
 
+    if let Some(path) = config {
+        load_config(path).await;
+    }
+
     if is_safe {
         if let Some(t) = get_tls_acceptor().await {
             let o = ServiceTls::new(MainService::new(*server.addr()), t);
@@ -308,26 +559,77 @@ async fn start_up(addr: &str, is_tool: bool, ipscope: &Vec<IpAddr>, is_safe: boo
     }
 }
 
+//"format json" or "format text" for replies on a connection, "json" before a sentence for its reply.
+fn to_format(s: &str) -> Option<bool> {
+    let mut iter = s.split_whitespace();
+    if iter.next() != Some(FORMAT) {
+        return None;
+    }
+    match (iter.next(), iter.next()) {
+        (Some(JSON), None) => Some(true),
+        (Some(TEXT), None) => Some(false),
+        _ => None,
+    }
+}
+
+//target like "127.0.0.1:20000", "[::1]:20000" or "a.com:20000".
+fn is_target(s: &str) -> bool {
+    s.parse::<SocketAddr>().is_ok()
+        || s.rsplit_once(':')
+            .is_some_and(|(h, p)| !h.is_empty() && !h.contains(':') && p.parse::<u16>().is_ok())
+}
+
+//proportion like "1:2:3".
+fn is_proportion(s: &str) -> bool {
+    s.split(':')
+        .all(|o| !o.is_empty() && o.bytes().all(|b| b.is_ascii_digit()))
+}
+
+//"auth <token>" authenticates a connection.
+fn to_token(s: &str) -> Option<&str> {
+    let mut iter = s.split_whitespace();
+    if iter.next() != Some(AUTH) {
+        return None;
+    }
+    match (iter.next(), iter.next()) {
+        (Some(o), None) => Some(o),
+        _ => Some(""),
+    }
+}
+
+///"json" is true if replies on the connection are JSON.
+///"user" is who the connection is authenticated as, "failures" are failed "auth" sentences.
 #[derive(Clone, Debug)]
 struct MainService {
     host: Arc<SocketAddr>,
+    json: bool,
+    user: Option<User>,
+    failures: u32,
 }
 
 #[async_trait]
 impl FuncRw for MainService {
-    async fn service(&mut self, req: &mut Vec<u8>, rsp: &mut Vec<u8>) {
+    ///sentences of a message are split by line feed, their replies are in the same order.
+    ///a line starting with '#' is comment.
+    ///sentences after too many failed "auth" aren't served, the connection is closed.
+    async fn service(&mut self, peer: SocketAddr, req: &mut Vec<u8>, rsp: &mut Vec<u8>) -> bool {
         let s = into_str(req);
         req.clear();
-        trace!("[{:?}]cfg:{:?}", &self.host, s);
-        match RuleType::try_from(s.as_str()) {
-            Ok(o) => {
-                o.handle(rsp).await;
+        for line in s
+            .lines()
+            .map(|o| o.trim())
+            .filter(|o| !o.is_empty() && !o.starts_with('#'))
+        {
+            if !rsp.is_empty() {
+                rsp.push(b'\n');
             }
-            Err(n) => {
-                check_safe!(rsp);
-                rsp.extend_from_slice(OUTCOMES[n]);
+            self.sentence(peer, line, rsp).await;
+            if self.failures >= AUTH_ATTEMPTS {
+                warn!("[{:?}]auth attempts, close {peer}", &self.host);
+                return false;
             }
         }
+        true
     }
 }
 
@@ -335,6 +637,147 @@ impl MainService {
     fn new(host: SocketAddr) -> Self {
         Self {
             host: Arc::new(host),
+            json: false,
+            user: None,
+            failures: 0,
+        }
+    }
+
+    async fn sentence(&mut self, peer: SocketAddr, s: &str, rsp: &mut Vec<u8>) {
+        if let Some(o) = to_token(s) {
+            trace!("[{:?}]auth", &self.host);
+            let reply = match auth::identify(o) {
+                Some(u) => {
+                    info!("[{:?}]auth: {u}", &self.host);
+                    self.user = Some(u);
+                    Reply::new(0)
+                }
+                None => {
+                    warn!("[{:?}]auth error", &self.host);
+                    self.user = None;
+                    self.failures += 1;
+                    Reply::new(10)
+                }
+            };
+            reply.write(rsp, self.json);
+            return;
+        }
+        trace!("[{:?}]{:?} cfg:{:?}", &self.host, &self.user, s);
+        if auth::is_required() && self.user.is_none() {
+            Reply::new(11).write(rsp, self.json);
+            return;
         }
+        if let Some(o) = to_format(s) {
+            self.json = o;
+            Reply::new(0).write(rsp, o);
+            return;
+        }
+        let (json, s) = match s.split_once(char::is_whitespace) {
+            Some((JSON, o)) => (true, o),
+            _ => (self.json, s),
+        };
+        let o = RuleType::try_from(s);
+        //every sentence except state changes servers, it's audited.
+        let audited = !matches!(o, Ok(RuleType::State(_)));
+        let server = o.as_ref().ok().and_then(|o| o.server());
+        let reply = match o {
+            Ok(o) if !self.is_allowed(&o) => Reply::error(12, redact(s)),
+            Ok(o) => o.handle().await,
+            Err((n, d)) => unsafe {
+                if SAFE {
+                    Reply::new(4)
+                } else {
+                    Reply::error(n, d)
+                }
+            },
+        };
+        if audited {
+            self.audit(peer, s, server, &reply).await;
+        }
+        reply.write(rsp, json);
+    }
+
+    //like '{"time":"2025-01-01 00:00:00","peer":"127.0.0.1:50000","user":"ops","role":"admin",
+    //"sentence":"shutdown 127.0.0.1:10000","code":0,"outcome":"ok","detail":"","server":"127.0.0.1:10000"}'
+    async fn audit(&self, peer: SocketAddr, s: &str, server: Option<String>, reply: &Reply) {
+        if !audit::is_enabled() {
+            return;
+        }
+        let (user, role) = match &self.user {
+            Some(u) => (json_str(u.name()), json_str(&u.role().to_string())),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        audit::record(format!(
+            "{{\"time\":{},\"peer\":\"{peer}\",\"user\":{user},\"role\":{role},\"sentence\":{},\"code\":{},\"outcome\":{},\"detail\":{},\"server\":{}}}",
+            json_str(&now_str()),
+            json_str(&redact(s)),
+            reply.code,
+            json_str(&into_str(OUTCOMES[reply.code])),
+            json_str(&reply.detail),
+            server.map(|o| json_str(&o)).unwrap_or("null".to_string())
+        ))
+        .await;
     }
+
+    //read-only user can only show state.
+    fn is_allowed(&self, o: &RuleType) -> bool {
+        match self.user.as_ref().map(|u| u.role()) {
+            Some(Role::Read) => matches!(o, RuleType::State(_)),
+            _ => true,
+        }
+    }
+}
+
+#[test]
+fn test() {
+    //every target has a proportion, a shorter list left the last targets without share.
+    let (a, p) = some_addr_proportion("127.0.0.1:1,127.0.0.1:2,127.0.0.1:3", Some("2"));
+    assert_eq!(a.len(), 3);
+    assert_eq!(p, [2, 1, 1]);
+    let (_, p) = some_addr_proportion("127.0.0.1:1,127.0.0.1:2", None);
+    assert_eq!(p, [1, 1]);
+
+    assert_eq!(to_format(" format json"), Some(true));
+    assert_eq!(to_format("format xml"), None);
+    assert_eq!(to_token("auth abc"), Some("abc"));
+    assert_eq!(to_token("auth"), Some(""));
+    assert_eq!(to_token("state"), None);
+    let o = RuleType::try_from("update 127.0.0.1:1 127.0.0.1:2,127.0.0.1:3 3");
+    assert!(matches!(o, Ok(RuleType::Update(_, a, p)) if a.len() == 2 && p == [3, 1]));
+    let e = RuleType::try_from("update 127.0.0.1 127.0.0.1:2").err();
+    assert_eq!(e, Some((3, "127.0.0.1".to_string())));
+    let e = RuleType::try_from("update 127.0.0.1:1 127.0.0.1:2 balance=x").err();
+    assert_eq!(e, Some((13, "balance=x".to_string())));
+    let e = RuleType::try_from("update 127.0.0.1:1 127.0.0.1:2 1 x").err();
+    assert_eq!(e, Some((13, "x".to_string())));
+    let e = RuleType::try_from("update 127.0.0.1:1 a.com:80,a.com 1:1").err();
+    assert_eq!(e, Some((3, "a.com".to_string())));
+    let e = RuleType::try_from("update 127.0.0.1:1 [::1]:2,[::1]:2").err();
+    assert_eq!(e, Some((3, "[::1]:2".to_string())));
+    assert!(RuleType::try_from("update 127.0.0.1:1 a.com:80,[::1]:2 1:2").is_ok());
+    assert_eq!(
+        redact("certificate f ./a.p12  pwd tls"),
+        "certificate f ./a.p12 *** tls"
+    );
+    assert_eq!(password_ref("certificate f ./a.p12", "pwd"), None);
+    assert_eq!(
+        password_ref("certificate f ./a.p12", "env:PWD"),
+        Some("env:PWD")
+    );
+    assert_eq!(
+        redact("certificate s 127.0.0.1:1 env:PWD"),
+        "certificate s 127.0.0.1:1 env:PWD"
+    );
+    let e = RuleType::try_from("tcp 127.0.0.1:1 127.0.0.1:2 1 balance=x").err();
+    assert_eq!(e, Some((9, "balance=x".to_string())));
+    let e = RuleType::try_from("tcp 127.0.0.1:1 127.0.0.1:2,127.0.0.1:2").err();
+    assert_eq!(e, Some((3, "127.0.0.1:2".to_string())));
+    let e = RuleType::try_from("http 127.0.0.1:1 127.0.0.1:2 host=a>127.0.0.1:3,127.0.0.1:3").err();
+    assert_eq!(e, Some((9, "host=a>127.0.0.1:3,127.0.0.1:3".to_string())));
+    let mut rsp = Vec::new();
+    Reply::error(3, "x".to_string()).write(&mut rsp, true);
+    assert_eq!(
+        rsp,
+        b"{\"code\":3,\"message\":\"SocketAddr error\",\"detail\":\"x\",\"data\":null}"
+    );
 }
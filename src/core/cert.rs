@@ -1,7 +1,17 @@
 use super::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{OnceCell, RwLock};
+use tokio::time::{Duration, timeout};
 use tokio_native_tls::native_tls::Identity;
 
+///length of frame head, a big-endian u32 which is the length of data.
+const FRAME_HEAD: usize = 4;
+
+///the maximum length of certificate data.
+const FRAME_MAX: usize = 1 << 20;
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
 static VALID_IDENTITY: OnceCell<RwLock<Vec<Identity>>> = OnceCell::const_new();
 
 async fn identity_vec() -> &'static RwLock<Vec<Identity>> {
@@ -47,9 +57,58 @@ pub(crate) async fn build_certificate_from_file(path: String, pwd: String) -> bo
     }
 }
 
-///input socket and  password
+///input socket, password and whether to use tls
 ///get certificate data from socket
 ///then build identity
-pub(crate) async fn build_certificate_from_socket(_soc: String, _pwd: String) -> bool {
-    false
+pub(crate) async fn build_certificate_from_socket(soc: String, pwd: String, tls: bool) -> bool {
+    if let Some(f) = get_socket_data(soc, tls).await {
+        build_identity(f.as_slice(), &pwd).await
+    } else {
+        false
+    }
+}
+
+async fn get_socket_data(soc: String, tls: bool) -> Option<Vec<u8>> {
+    let host = soc
+        .rsplit_once(':')
+        .map(|o| o.0)
+        .unwrap_or_default()
+        .to_string();
+    let mut client = Client::new(Remote::new(Protoc::TCP, soc, host));
+    let o = if tls {
+        let s = client.tls_stream().await?;
+        timeout(RECEIVE_TIMEOUT, receive_frame(s)).await
+    } else {
+        let s = client.tcp_stream().await?;
+        timeout(RECEIVE_TIMEOUT, receive_frame(s)).await
+    };
+    o.map_err(|e| error!("get socket data: {e}")).ok().flatten()
+}
+
+///a frame is a big-endian u32 length and data.
+async fn receive_frame<T>(mut s: BufStream<T>) -> Option<Vec<u8>>
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    loop {
+        let buf = s.r_buf_mut();
+        if buf.len() >= FRAME_HEAD {
+            let mut head = [0; FRAME_HEAD];
+            head.copy_from_slice(&buf[..FRAME_HEAD]);
+            let n = u32::from_be_bytes(head) as usize;
+            if n > FRAME_MAX {
+                error!("frame too long: {n}");
+                return None;
+            }
+            if buf.len() >= FRAME_HEAD + n {
+                trace!("receive frame: {n}");
+                return Some(buf[FRAME_HEAD..FRAME_HEAD + n].to_vec());
+            }
+        }
+        if s.r_f() {
+            error!("frame incomplete");
+            return None;
+        }
+        s.read().await;
+    }
 }
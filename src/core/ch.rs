@@ -0,0 +1,150 @@
+use super::*;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, Instant, sleep};
+
+const HANDSHAKE: u8 = 0x16;
+const CLIENT_HELLO: usize = 0x01;
+const SERVER_NAME: usize = 0x0000;
+const ALPN: usize = 0x0010;
+const HOST_NAME: usize = 0x00;
+
+///the maximum length of ClientHello record.
+const RECORD_MAX: usize = 16384 + 5;
+
+///server name and ALPN protocols of TLS ClientHello.
+#[derive(Debug, Default, Getters, PartialEq)]
+pub(crate) struct ClientHello {
+    #[getset(get = "pub(crate)")]
+    sni: Option<String>,
+    #[getset(get = "pub(crate)")]
+    alpn: Vec<String>,
+}
+
+impl ClientHello {
+    ///parse a TLS record, none if the record is incomplete.
+    ///"Some(None)" if it isn't a ClientHello.
+    pub(crate) fn parse(buf: &[u8]) -> Option<Option<Self>> {
+        if buf.first().is_some_and(|n| *n != HANDSHAKE) {
+            return Some(None);
+        }
+        let mut i = 3;
+        let len = u16_at(buf, &mut i)?;
+        let record = buf.get(5..5 + len)?;
+        Some(Self::handshake(record))
+    }
+
+    //"msg_type length version random session_id cipher_suites compression_methods extensions"
+    fn handshake(buf: &[u8]) -> Option<Self> {
+        let mut i = 0;
+        if u8_at(buf, &mut i)? != CLIENT_HELLO {
+            return None;
+        }
+        bytes(buf, &mut i, 3 + 2 + 32)?;
+        let n = u8_at(buf, &mut i)?;
+        bytes(buf, &mut i, n)?;
+        let n = u16_at(buf, &mut i)?;
+        bytes(buf, &mut i, n)?;
+        let n = u8_at(buf, &mut i)?;
+        bytes(buf, &mut i, n)?;
+        let mut o = Self::default();
+        if i == buf.len() {
+            return Some(o);
+        }
+        let n = u16_at(buf, &mut i)?;
+        let ext = bytes(buf, &mut i, n)?;
+
+        let mut i = 0;
+        while i < ext.len() {
+            let t = u16_at(ext, &mut i)?;
+            let n = u16_at(ext, &mut i)?;
+            let data = bytes(ext, &mut i, n)?;
+            match t {
+                SERVER_NAME => o.sni = host_name(data),
+                ALPN => o.alpn = protocols(data)?,
+                _ => {}
+            }
+        }
+        Some(o)
+    }
+
+    ///peek ClientHello from "s", the stream is not consumed.
+    pub(crate) async fn peek(s: &TcpStream, wait: Duration) -> Option<Self> {
+        let deadline = Instant::now() + wait;
+        let mut buf = vec![0; RECORD_MAX];
+        loop {
+            let n = s
+                .peek(&mut buf)
+                .await
+                .map_err(|e| error!("peek client hello: {e}"))
+                .ok()?;
+            if let Some(o) = Self::parse(&buf[..n]) {
+                return o;
+            }
+            if n == 0 || n == RECORD_MAX || Instant::now() >= deadline {
+                return None;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+//"server_name_list", the first host name.
+fn host_name(buf: &[u8]) -> Option<String> {
+    let mut i = 2;
+    while i < buf.len() {
+        let kind = u8_at(buf, &mut i)?;
+        let n = u16_at(buf, &mut i)?;
+        let name = bytes(buf, &mut i, n)?;
+        if kind == HOST_NAME {
+            return Some(into_str(name));
+        }
+    }
+    None
+}
+
+//"protocol_name_list"
+fn protocols(buf: &[u8]) -> Option<Vec<String>> {
+    let mut v = Vec::new();
+    let mut i = 2;
+    while i < buf.len() {
+        let n = u8_at(buf, &mut i)?;
+        v.push(into_str(bytes(buf, &mut i, n)?));
+    }
+    Some(v)
+}
+
+//take "n" bytes from "buf" at "i".
+fn bytes<'a>(buf: &'a [u8], i: &mut usize, n: usize) -> Option<&'a [u8]> {
+    let o = buf.get(*i..*i + n)?;
+    *i += n;
+    Some(o)
+}
+
+fn u8_at(buf: &[u8], i: &mut usize) -> Option<usize> {
+    bytes(buf, i, 1).map(|o| o[0] as usize)
+}
+
+fn u16_at(buf: &[u8], i: &mut usize) -> Option<usize> {
+    bytes(buf, i, 2).map(|o| u16::from_be_bytes([o[0], o[1]]) as usize)
+}
+
+#[test]
+fn test() {
+    //ClientHello with server name "a.com" and ALPN "h2".
+    let mut ext = vec![0, 0, 0, 10, 0, 8, HOST_NAME as u8, 0, 5];
+    ext.extend_from_slice(b"a.com");
+    ext.extend([0, ALPN as u8, 0, 5, 0, 3, 2]);
+    ext.extend_from_slice(b"h2");
+    let mut hello = vec![CLIENT_HELLO as u8, 0, 0, 0, 3, 3];
+    hello.extend([0; 32]);
+    hello.extend([0, 0, 2, 0x13, 0x01, 1, 0, 0, ext.len() as u8]);
+    hello.extend(ext);
+    let mut record = vec![HANDSHAKE, 3, 1, 0, hello.len() as u8];
+    record.extend(hello);
+
+    let o = ClientHello::parse(&record).flatten().unwrap();
+    assert_eq!(o.sni().as_deref(), Some("a.com"));
+    assert_eq!(o.alpn(), &["h2"]);
+    assert_eq!(ClientHello::parse(&record[..20]), None);
+    assert_eq!(ClientHello::parse(b"GET / HTTP/1.1"), Some(None));
+}
@@ -1,5 +1,24 @@
 use super::*;
 use httpenergy::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+///the maximum length of request header.
+pub(crate) const HEAD_MAX: usize = 65536;
+
+///length of request header including the empty line, none if it's incomplete.
+pub(crate) fn head_len(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|n| n + 4)
+}
+
+///read until request header is complete, or stream ends, or header is too long.
+pub(crate) async fn read_head<T>(s: &mut BufStream<T>)
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    while head_len(s.r_buf_mut()).is_none() && !s.r_f() && s.r_buf_mut().len() < HEAD_MAX {
+        s.read().await;
+    }
+}
 
 pub(crate) struct HttpRequest<'a> {
     req: H1RequestUnits,
@@ -41,3 +60,154 @@ impl<'a> HttpRequest<'a> {
         self.get_header_value("Host")
     }
 }
+
+///header of request or response which can be rewritten.
+#[derive(Debug)]
+pub(crate) struct HttpHead {
+    line: Vec<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpHead {
+    ///parse header, return it and its length.
+    ///None if incomplete, or malformed, like a bare CR or LF, a line without colon,
+    ///or whitespace before colon.
+    pub(crate) fn parse(buf: &[u8]) -> Option<(Self, usize)> {
+        let n = head_len(buf)?;
+        let s = into_str(&buf[..n - 4]);
+        if s.split("\r\n").any(|l| l.contains(['\r', '\n'])) {
+            return None;
+        }
+        let mut lines = s.split("\r\n");
+        let line: Vec<String> = lines
+            .next()?
+            .splitn(3, ' ')
+            .map(|s| s.to_string())
+            .collect();
+        if line.len() < 2 {
+            return None;
+        }
+        let mut headers = Vec::new();
+        for l in lines {
+            let (k, v) = l.split_once(':')?;
+            if k.is_empty() || k.contains(|c: char| c.is_ascii_whitespace()) {
+                return None;
+            }
+            headers.push((k.to_string(), v.trim().to_string()));
+        }
+        Some((Self { line, headers }, n))
+    }
+
+    ///method of request, or version of response.
+    pub(crate) fn first(&self) -> &str {
+        &self.line[0]
+    }
+
+    ///target of request, or status of response.
+    pub(crate) fn second(&self) -> &str {
+        &self.line[1]
+    }
+
+    ///version of request, or reason of response.
+    pub(crate) fn third(&self) -> &str {
+        self.line.get(2).map_or("", |s| s.as_str())
+    }
+
+    pub(crate) fn set_second(&mut self, s: String) {
+        self.line[1] = s;
+    }
+
+    pub(crate) fn header(&self, k: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|o| o.0.eq_ignore_ascii_case(k))
+            .map(|o| o.1.as_str())
+    }
+
+    ///values of all headers named "k".
+    pub(crate) fn headers(&self, k: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|o| o.0.eq_ignore_ascii_case(k))
+            .map(|o| o.1.as_str())
+            .collect()
+    }
+
+    ///header names listed in every "Connection" header, in lowercase.
+    pub(crate) fn connection_options(&self) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|o| o.0.eq_ignore_ascii_case("Connection"))
+            .flat_map(|o| o.1.split(','))
+            .map(|s| s.trim().to_ascii_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    ///remove all headers named "k".
+    pub(crate) fn remove_header(&mut self, k: &str) {
+        self.headers.retain(|o| !o.0.eq_ignore_ascii_case(k));
+    }
+
+    ///replace headers named "k" with one header.
+    pub(crate) fn set_header(&mut self, k: &str, v: &str) {
+        self.remove_header(k);
+        self.append_header(k, v);
+    }
+
+    pub(crate) fn append_header(&mut self, k: &str, v: &str) {
+        self.headers.push((k.to_string(), v.to_string()));
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut s = self.line.join(" ");
+        s.push_str("\r\n");
+        for (k, v) in &self.headers {
+            s.push_str(k);
+            s.push_str(": ");
+            s.push_str(v);
+            s.push_str("\r\n");
+        }
+        s.push_str("\r\n");
+        s.into_bytes()
+    }
+}
+
+///split absolute uri like "http://host:port/path" into scheme, authority and origin form.
+pub(crate) fn split_uri(s: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = s.split_once("://")?;
+    let (authority, path) = rest.find('/').map_or((rest, "/"), |n| rest.split_at(n));
+    let authority = authority.rsplit_once('@').map_or(authority, |o| o.1);
+    if authority.is_empty() {
+        return None;
+    }
+    Some((scheme, authority, path))
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        split_uri("http://u:p@example.com:8080/a?b"),
+        Some(("http", "example.com:8080", "/a?b"))
+    );
+    assert_eq!(
+        split_uri("http://example.com"),
+        Some(("http", "example.com", "/"))
+    );
+    assert_eq!(split_uri("/a"), None);
+
+    let buf =
+        b"GET http://example.com/a HTTP/1.1\r\nHost: x\r\nProxy-Connection: keep-alive\r\n\r\nbody";
+    let (mut head, n) = HttpHead::parse(buf).unwrap();
+    assert_eq!(&buf[n..], b"body");
+    head.set_second("/a".to_string());
+    head.set_header("host", "example.com");
+    head.remove_header("proxy-connection");
+    assert_eq!(
+        head.to_bytes(),
+        b"GET /a HTTP/1.1\r\nhost: example.com\r\n\r\n"
+    );
+    let buf = b"GET / HTTP/1.1\r\nConnection: keep-alive, X-A\r\nconnection: x-b\r\n\r\n";
+    let (head, _) = HttpHead::parse(buf).unwrap();
+    assert_eq!(head.connection_options(), ["keep-alive", "x-a", "x-b"]);
+}
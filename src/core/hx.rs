@@ -0,0 +1,484 @@
+use super::*;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{Duration, timeout};
+
+const BAD_GATEWAY: &[u8] =
+    b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+const BAD_REQUEST: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+///transfer-codings which can come before "chunked".
+const CODINGS: [&str; 5] = ["gzip", "x-gzip", "deflate", "compress", "x-compress"];
+
+///the maximum length of a line in chunked body.
+const LINE_MAX: usize = 4096;
+
+///choose remote for every request on a keep-alive connection.
+#[async_trait]
+pub(crate) trait FuncHttp: Send {
+    ///choose for request whose header is at the start of "buf", header can be rewritten.
+    ///requests of the same key share a connection, none if request can't be routed.
+    async fn choose(&mut self, buf: &mut Vec<u8>) -> Option<String>;
+
+    ///connect to what is chosen, return target and stream.
+    async fn connect(&mut self) -> Option<(String, Upstream)>;
+
+    ///meter of "target" which is connected, bytes to and from it are added as they pass.
+    fn backend(&self, _target: &str) -> Option<Arc<Meter>> {
+        None
+    }
+
+    ///connection to target ends.
+    async fn done(&mut self, target: &str);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Chunk {
+    Size,
+    ///bytes left.
+    Data(usize),
+    ///bytes of CRLF left.
+    DataEnd(usize),
+    Trailer,
+}
+
+///framing of message body.
+#[derive(Debug, PartialEq)]
+enum Body {
+    ///bytes left.
+    Length(usize),
+    Chunked(Chunk, Vec<u8>),
+    ///until stream ends.
+    Close,
+    ///framing is ambiguous or broken, message can't be relayed.
+    Invalid,
+}
+
+impl Body {
+    //request without length has no body.
+    fn request(head: &HttpHead) -> Self {
+        Self::framing(head).unwrap_or(Self::Length(0))
+    }
+
+    fn response(head: &HttpHead, method: &str) -> Self {
+        let status = head.second();
+        if method == "HEAD" || status == "204" || status == "304" || status.starts_with('1') {
+            return Self::Length(0);
+        }
+        Self::framing(head).unwrap_or(Self::Close)
+    }
+
+    //none without framing headers, invalid if they conflict, repeat or can't be parsed.
+    fn framing(head: &HttpHead) -> Option<Self> {
+        let te = head.headers("Transfer-Encoding");
+        let cl = head.headers("Content-Length");
+        if !te.is_empty() {
+            if !cl.is_empty() {
+                return Some(Self::Invalid);
+            }
+            let codings: Vec<String> = te
+                .iter()
+                .flat_map(|v| v.split(','))
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            return match codings.split_last() {
+                Some((last, rest))
+                    if last == "chunked" && rest.iter().all(|s| CODINGS.contains(&s.as_str())) =>
+                {
+                    Some(Self::Chunked(Chunk::Size, Vec::new()))
+                }
+                _ => Some(Self::Invalid),
+            };
+        }
+        match cl[..] {
+            [] => None,
+            [v] if is_digits(v, 10) => Some(v.parse().map_or(Self::Invalid, Self::Length)),
+            _ => Some(Self::Invalid),
+        }
+    }
+
+    fn is_invalid(&self) -> bool {
+        *self == Self::Invalid
+    }
+
+    ///consume "buf", return length of body in it if body ends.
+    fn feed(&mut self, buf: &[u8]) -> Option<usize> {
+        let (state, line) = match self {
+            Self::Length(n) => {
+                let k = buf.len().min(*n);
+                *n -= k;
+                return (*n == 0).then_some(k);
+            }
+            Self::Close | Self::Invalid => return None,
+            Self::Chunked(state, line) => (state, line),
+        };
+        let mut i = 0;
+        let mut invalid = false;
+        while i < buf.len() {
+            match *state {
+                Chunk::Data(n) => {
+                    let k = (buf.len() - i).min(n);
+                    i += k;
+                    *state = if k == n {
+                        Chunk::DataEnd(2)
+                    } else {
+                        Chunk::Data(n - k)
+                    };
+                }
+                Chunk::DataEnd(n) => {
+                    //data ends with CRLF.
+                    if buf[i] != b"\r\n"[2 - n] {
+                        invalid = true;
+                        break;
+                    }
+                    i += 1;
+                    *state = if n > 1 {
+                        Chunk::DataEnd(n - 1)
+                    } else {
+                        Chunk::Size
+                    };
+                }
+                Chunk::Size | Chunk::Trailer => {
+                    let b = buf[i];
+                    i += 1;
+                    if b != b'\n' {
+                        if line.len() < LINE_MAX {
+                            line.push(b);
+                        }
+                        continue;
+                    }
+                    let s = into_str(line);
+                    line.clear();
+                    let s = s.trim();
+                    if *state == Chunk::Trailer {
+                        if s.is_empty() {
+                            return Some(i);
+                        }
+                    } else {
+                        let s = s.split(';').next().unwrap_or_default().trim();
+                        *state = match usize::from_str_radix(s, 16) {
+                            Ok(0) if is_digits(s, 16) => Chunk::Trailer,
+                            Ok(n) if is_digits(s, 16) => Chunk::Data(n),
+                            _ => {
+                                invalid = true;
+                                break;
+                            }
+                        };
+                    }
+                }
+            }
+        }
+        if invalid {
+            *self = Self::Invalid;
+        }
+        None
+    }
+}
+
+//"s" is not empty and has only digits of "radix", no sign.
+fn is_digits(s: &str, radix: u32) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_digit(radix))
+}
+
+//feed body from "start" of "buf", bytes after the body are split off.
+fn split(body: &mut Body, buf: &mut Vec<u8>, start: usize) -> Option<Vec<u8>> {
+    body.feed(&buf[start..]).map(|k| buf.split_off(start + k))
+}
+
+//HTTP/1.1 is keep-alive unless "Connection: close", HTTP/1.0 needs "Connection: keep-alive".
+fn keep_alive(version: &str, head: &HttpHead) -> bool {
+    let c = head
+        .header("Connection")
+        .map(|v| v.to_ascii_lowercase())
+        .unwrap_or_default();
+    if version == "HTTP/1.0" {
+        c.contains("keep-alive")
+    } else {
+        !c.contains("close")
+    }
+}
+
+///what follows an exchange.
+#[derive(Debug, PartialEq)]
+enum Next {
+    KeepAlive,
+    Close,
+    Upgrade,
+}
+
+//send request at the start of server buffer to remote, and relay its response.
+//bytes of next request are kept in server buffer, bytes after response are dropped.
+//headers can be edited by data funcs, body is framed by headers which are sent.
+async fn exchange<T>(
+    server: &mut BufStream<T>,
+    remote: &mut Upstream,
+    peer: SocketAddr,
+    tls: bool,
+    server_data_func: &mut impl FuncR,
+    remote_data_func: &mut impl FuncR,
+) -> Next
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    let mut buf = server.take_buf();
+    let (mut head, mut n) = if let Some(o) = HttpHead::parse(&buf) {
+        o
+    } else {
+        if head_len(&buf).is_some() {
+            debug!("invalid request header");
+            server.write(BAD_REQUEST.to_vec()).await;
+        }
+        return Next::Close;
+    };
+    if server_data_func.head(&mut head, peer, tls).await {
+        let mut v = head.to_bytes();
+        let k = v.len();
+        v.extend_from_slice(&buf[n..]);
+        buf = v;
+        n = k;
+    }
+    let method = head.first().to_string();
+    let mut keep = keep_alive(head.third(), &head);
+    let mut req = Body::request(&head);
+    if req.is_invalid() {
+        debug!("invalid request framing");
+        server.write(BAD_REQUEST.to_vec()).await;
+        return Next::Close;
+    }
+    let rest = split(&mut req, &mut buf, n);
+    if req.is_invalid() {
+        debug!("invalid request body");
+        server.write(BAD_REQUEST.to_vec()).await;
+        return Next::Close;
+    }
+    let mut req_end = rest.is_some();
+    if let Some(v) = rest {
+        *server.r_buf_mut() = v;
+    }
+    server_data_func.data(&mut buf).await;
+    remote.write(buf).await;
+
+    let mut res: Option<Body> = None;
+    let mut res_end = false;
+    loop {
+        if req_end && res_end {
+            return if keep { Next::KeepAlive } else { Next::Close };
+        }
+        if server.w_f() || remote.w_f() {
+            return Next::Close;
+        }
+        tokio::select! {
+            _ = server.read(), if !req_end => {
+                let mut v = server.take_buf();
+                if let Some(rest) = split(&mut req, &mut v, 0) {
+                    *server.r_buf_mut() = rest;
+                    req_end = true;
+                }
+                if req.is_invalid() {
+                    debug!("invalid request body");
+                    return Next::Close;
+                }
+                if !v.is_empty() {
+                    server_data_func.data(&mut v).await;
+                    remote.write(v).await;
+                }
+                if server.r_f() && !req_end {
+                    debug!("server end in request");
+                    return Next::Close;
+                }
+            }
+            _ = remote.read() => {
+                loop {
+                    let body = if let Some(b) = &mut res {
+                        b
+                    } else {
+                        let (mut head, n) = if let Some(o) = HttpHead::parse(remote.r_buf_mut()) {
+                            o
+                        } else {
+                            if remote.r_buf_mut().len() >= HEAD_MAX
+                                || head_len(remote.r_buf_mut()).is_some()
+                            {
+                                return Next::Close;
+                            }
+                            break;
+                        };
+                        let mut v: Vec<u8> = remote.r_buf_mut().drain(..n).collect();
+                        let status = head.second().to_string();
+                        //interim response like "100 Continue" is untouched.
+                        if !status.starts_with('1') {
+                            if remote_data_func.head(&mut head, peer, tls).await {
+                                v = head.to_bytes();
+                            }
+                            keep = keep && keep_alive(head.first(), &head);
+                            let b = Body::response(&head, &method);
+                            if b.is_invalid() {
+                                debug!("invalid response framing");
+                                server.write(BAD_GATEWAY.to_vec()).await;
+                                return Next::Close;
+                            }
+                            res = Some(b);
+                        }
+                        remote_data_func.data(&mut v).await;
+                        server.write(v).await;
+                        if status == "101" {
+                            return Next::Upgrade;
+                        }
+                        match &mut res {
+                            Some(b) => b,
+                            None => continue,
+                        }
+                    };
+                    let mut v = remote.take_buf();
+                    res_end = split(body, &mut v, 0).is_some();
+                    if body.is_invalid() {
+                        debug!("invalid response body");
+                        return Next::Close;
+                    }
+                    if !v.is_empty() {
+                        remote_data_func.data(&mut v).await;
+                        server.write(v).await;
+                    }
+                    break;
+                }
+                if remote.r_f() && !res_end {
+                    debug!("remote end in response");
+                    return Next::Close;
+                }
+            }
+        }
+    }
+}
+
+//an idle connection may be closed by remote, it's checked without waiting.
+async fn is_open(s: &mut Upstream) -> bool {
+    let _ = timeout(Duration::ZERO, s.read()).await;
+    !s.r_f() && !s.w_f() && s.is_empty()
+}
+
+///route every request of a keep-alive connection on its own.
+///connection to remote is reused by next request of the same key, otherwise it's switched.
+///"peer" is address of client, "tls" is true if client connects by TLS.
+pub(crate) async fn http_loop<T>(
+    server: &mut BufStream<T>,
+    peer: SocketAddr,
+    tls: bool,
+    func: &mut impl FuncHttp,
+    server_data_func: &mut impl FuncR,
+    remote_data_func: &mut impl FuncR,
+) where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    //key, target and stream.
+    let mut remote: Option<(String, String, Upstream)> = None;
+    loop {
+        read_head(server).await;
+        if head_len(server.r_buf_mut()).is_none() {
+            break;
+        }
+        let key = if let Some(k) = func.choose(server.r_buf_mut()).await {
+            k
+        } else {
+            break;
+        };
+
+        let reuse = if let Some((k, _, s)) = &mut remote {
+            *k == key && is_open(s).await
+        } else {
+            false
+        };
+        if !reuse {
+            if let Some((_, t, _)) = remote.take() {
+                func.done(&t).await;
+            }
+            if let Some((t, s)) = func.connect().await {
+                debug!("http[{key}] remote[{t}]");
+                let (to, from) = func.backend(&t).map(Flow::backend_pair).unzip();
+                server_data_func.set_backend(to);
+                remote_data_func.set_backend(from);
+                remote = Some((key, t, s));
+            } else {
+                server.write(BAD_GATEWAY.to_vec()).await;
+                break;
+            }
+        }
+
+        let s = if let Some((_, _, s)) = &mut remote {
+            s
+        } else {
+            break;
+        };
+        match exchange(server, s, peer, tls, server_data_func, remote_data_func).await {
+            Next::KeepAlive => {}
+            Next::Close => break,
+            Next::Upgrade => {
+                debug!("http upgrade");
+                if s.has_remaining() {
+                    let mut v = s.take_buf();
+                    remote_data_func.data(&mut v).await;
+                    server.write(v).await;
+                }
+                read_loop(server, s, server_data_func, remote_data_func).await;
+                break;
+            }
+        }
+    }
+    if let Some((_, t, _)) = remote {
+        func.done(&t).await;
+    }
+}
+
+#[test]
+fn test() {
+    let mut b = Body::Length(5);
+    assert_eq!(b.feed(b"abc"), None);
+    assert_eq!(b.feed(b"defg"), Some(2));
+
+    let buf = b"4;x=1\r\nabcd\r\n10\r\n0123456789abcdef\r\n0\r\nT: 1\r\n\r\nGET";
+    let mut b = Body::Chunked(Chunk::Size, Vec::new());
+    assert_eq!(b.feed(&buf[..9]), None);
+    assert_eq!(b.feed(&buf[9..]).map(|n| n + 9), Some(buf.len() - 3));
+
+    let (head, _) = HttpHead::parse(b"HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\n\r\n").unwrap();
+    assert!(keep_alive(head.first(), &head));
+    assert_eq!(Body::response(&head, "GET"), Body::Close);
+    assert_eq!(Body::response(&head, "HEAD"), Body::Length(0));
+    let (head, _) = HttpHead::parse(b"POST / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    assert!(!keep_alive(head.third(), &head));
+    assert_eq!(Body::request(&head), Body::Length(0));
+
+    let mut b = Body::Chunked(Chunk::Size, Vec::new());
+    assert_eq!(b.feed(b"zz\r\nabc"), None);
+    assert!(b.is_invalid());
+    let mut b = Body::Chunked(Chunk::Size, Vec::new());
+    assert_eq!(b.feed(b"+2\r\nab\r\n0\r\n\r\n"), None);
+    assert!(b.is_invalid());
+    let mut b = Body::Chunked(Chunk::Size, Vec::new());
+    assert_eq!(b.feed(b"2\r\nabc\r\n0\r\n\r\n"), None);
+    assert!(b.is_invalid());
+    let framing = |s: &str| {
+        let s = format!("POST / HTTP/1.1\r\n{s}\r\n\r\n");
+        Body::request(&HttpHead::parse(s.as_bytes()).unwrap().0)
+    };
+    assert_eq!(framing("Content-Length: 5"), Body::Length(5));
+    assert!(framing("Content-Length: x").is_invalid());
+    assert!(framing("Content-Length: +5").is_invalid());
+    assert!(framing("Content-Length: 5\r\nContent-Length: 5").is_invalid());
+    assert!(framing("Content-Length: 5, 5").is_invalid());
+    assert!(framing("Transfer-Encoding: xchunked").is_invalid());
+    assert!(framing("Transfer-Encoding: chunked, identity").is_invalid());
+    assert!(framing("Transfer-Encoding: chunked\r\nContent-Length: 5").is_invalid());
+    assert!(!framing("Transfer-Encoding: gzip\r\nTransfer-Encoding: Chunked").is_invalid());
+    let parse = |s: &str| HttpHead::parse(format!("POST / HTTP/1.1\r\n{s}\r\n\r\n").as_bytes());
+    assert!(parse("Transfer-Encoding : chunked").is_none());
+    assert!(parse("Transfer-Encoding\tchunked").is_none());
+    assert!(parse("Transfer-Encoding: chunked\r\n chunked").is_none());
+    assert!(parse("X: 1\nTransfer-Encoding: chunked").is_none());
+    assert!(parse("X: 1\rTransfer-Encoding: chunked").is_none());
+    assert!(parse(": chunked").is_none());
+    assert!(parse("Transfer-Encoding:chunked").is_some());
+}
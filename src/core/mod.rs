@@ -1,23 +1,42 @@
 mod cert;
+mod ch;
 mod hr;
+mod hx;
+mod mt;
 mod pd;
 mod pdtrait;
+mod px;
 mod rt;
 mod rw;
 mod sv;
+mod ud;
 
 pub(crate) use cert::*;
+pub(crate) use ch::*;
 pub(crate) use getset::{CopyGetters, Getters, MutGetters, Setters};
 pub(crate) use hr::*;
+pub(crate) use hx::*;
 pub(crate) use log::{debug, error, info, trace, warn};
+pub(crate) use mt::*;
 pub(crate) use pd::*;
 pub(crate) use pdtrait::*;
+pub(crate) use px::*;
 pub(crate) use rt::*;
 pub(crate) use rw::*;
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
 pub(crate) use sv::*;
 use time::{OffsetDateTime, UtcOffset};
+pub(crate) use ud::*;
+
+pub(crate) const HTTP: &str = "http";
+pub(crate) const HTTP_PT: &str = "http_pt";
+pub(crate) const TCP: &str = "tcp";
+pub(crate) const TLS: &str = "tls";
+pub(crate) const UDP: &str = "udp";
+pub(crate) const SOCKS5: &str = "socks5";
+pub(crate) const SNI: &str = "sni";
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub(crate) enum Protoc {
@@ -26,6 +45,23 @@ pub(crate) enum Protoc {
     UDP,
     HTTP,
     HTTPPT,
+    SOCKS5,
+    SNI,
+}
+
+impl fmt::Display for Protoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::TCP => TCP,
+            Self::TLS => TLS,
+            Self::UDP => UDP,
+            Self::HTTP => HTTP,
+            Self::HTTPPT => HTTP_PT,
+            Self::SOCKS5 => SOCKS5,
+            Self::SNI => SNI,
+        };
+        f.write_str(s)
+    }
 }
 
 #[derive(CopyGetters, Getters)]
@@ -68,6 +104,36 @@ pub(crate) fn into_str(buf: &[u8]) -> String {
     t.0
 }
 
+///JSON string of "s" with quotes.
+pub(crate) fn json_str(s: &str) -> String {
+    let mut o = String::with_capacity(s.len() + 2);
+    o.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => o.push_str("\\\""),
+            '\\' => o.push_str("\\\\"),
+            '\n' => o.push_str("\\n"),
+            '\r' => o.push_str("\\r"),
+            '\t' => o.push_str("\\t"),
+            c if (c as u32) < 0x20 => o.push_str(&format!("\\u{:04x}", c as u32)),
+            c => o.push(c),
+        }
+    }
+    o.push('"');
+    o
+}
+
+///host of "addr" without port and brackets, like "a.com" of "a.com:80" and "::1" of "[::1]:80".
+pub(crate) fn host_of(addr: &str) -> &str {
+    if let Some(s) = addr.strip_prefix('[') {
+        return s.split_once(']').map_or(s, |o| o.0);
+    }
+    match addr.split_once(':') {
+        Some((h, p)) if !p.contains(':') => h,
+        _ => addr,
+    }
+}
+
 ///get file by "path".
 ///if it's a file path, read it to "Vec".
 ///if it's a dir path, read the first file in the dir to "Vec".
@@ -154,4 +220,10 @@ fn test() {
     let mut v = vec![2, 4, 6];
     divide(&mut v);
     assert_eq!(v, [1, 2, 3]);
+    assert_eq!(json_str("a\"b\\\n\u{1}"), "\"a\\\"b\\\\\\n\\u0001\"");
+    assert_eq!(host_of("a.com:8080"), "a.com");
+    assert_eq!(host_of("a.com"), "a.com");
+    assert_eq!(host_of("[::1]:8080"), "::1");
+    assert_eq!(host_of("[::1]"), "::1");
+    assert_eq!(host_of("::1"), "::1");
 }
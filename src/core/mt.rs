@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+///upper bounds of connect latency histogram in seconds.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+///counters which are reported, name, type and help.
+const FAMILIES: [(&str, &str, &str); 5] = [
+    (
+        "connections_total",
+        "counter",
+        "connections accepted or connected",
+    ),
+    ("active_connections", "gauge", "connections which are open"),
+    (
+        "received_bytes_total",
+        "counter",
+        "bytes received from peers",
+    ),
+    ("sent_bytes_total", "counter", "bytes sent to peers"),
+    (
+        "connect_failures_total",
+        "counter",
+        "connections to targets which fail",
+    ),
+];
+
+//listener and target, target of listener itself is empty.
+type Meters = BTreeMap<(String, String), Arc<Meter>>;
+
+static METERS: OnceLock<RwLock<Meters>> = OnceLock::new();
+
+fn meters() -> &'static RwLock<Meters> {
+    METERS.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+///counters of a listener, or a backend which a listener connects to.
+///bytes of listener are from and to clients, bytes of backend are from and to the backend.
+#[derive(Debug, Default)]
+pub(crate) struct Meter {
+    connections: AtomicU64,
+    active: AtomicU64,
+    received: AtomicU64,
+    sent: AtomicU64,
+    failures: AtomicU64,
+    buckets: [AtomicU64; BUCKETS.len()],
+    ///micro seconds.
+    latency_sum: AtomicU64,
+    latency_count: AtomicU64,
+}
+
+impl Meter {
+    pub(crate) fn open(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn close(&self) {
+        let _ = self
+            .active
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    pub(crate) fn add(&self, received: usize, sent: usize) {
+        self.received.fetch_add(received as u64, Ordering::Relaxed);
+        self.sent.fetch_add(sent as u64, Ordering::Relaxed);
+    }
+
+    ///bytes received and sent.
+    pub(crate) fn bytes(&self) -> (u64, u64) {
+        (
+            self.received.load(Ordering::Relaxed),
+            self.sent.load(Ordering::Relaxed),
+        )
+    }
+
+    fn fail(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn observe(&self, d: Duration) {
+        let s = d.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|b| s <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_sum
+            .fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn values(&self) -> [u64; 5] {
+        [
+            &self.connections,
+            &self.active,
+            &self.received,
+            &self.sent,
+            &self.failures,
+        ]
+        .map(|o| o.load(Ordering::Relaxed))
+    }
+}
+
+///bytes of one direction on a listener or backend, "true" is received from its peers.
+#[derive(Clone, Debug)]
+pub(crate) struct Flow(Arc<Meter>, bool);
+
+impl Flow {
+    ///flow from clients, and flow to clients.
+    pub(crate) fn pair(m: Arc<Meter>) -> (Self, Self) {
+        (Self(m.clone(), true), Self(m, false))
+    }
+
+    ///flow to backend, and flow from backend.
+    pub(crate) fn backend_pair(m: Arc<Meter>) -> (Self, Self) {
+        (Self(m.clone(), false), Self(m, true))
+    }
+
+    pub(crate) fn add(&self, n: usize) {
+        if self.1 {
+            self.0.add(n, 0);
+        } else {
+            self.0.add(0, n);
+        }
+    }
+}
+
+fn find(listener: &str, target: &str) -> Option<Arc<Meter>> {
+    let k = (listener.to_string(), target.to_string());
+    meters().read().ok()?.get(&k).cloned()
+}
+
+fn meter(listener: &str, target: &str) -> Arc<Meter> {
+    if let Some(o) = find(listener, target) {
+        return o;
+    }
+    let k = (listener.to_string(), target.to_string());
+    meters()
+        .write()
+        .map(|mut m| m.entry(k).or_default().clone())
+        .unwrap_or_default()
+}
+
+///meter of listener "addr".
+pub(crate) fn listener_meter(addr: &str) -> Arc<Meter> {
+    meter(addr, "")
+}
+
+///meter of "target" which listener "addr" connects to.
+pub(crate) fn backend_meter(addr: &str, target: &str) -> Arc<Meter> {
+    meter(addr, target)
+}
+
+///meter of "target" if it isn't removed with its listener.
+pub(crate) fn find_backend_meter(addr: &str, target: &str) -> Option<Arc<Meter>> {
+    find(addr, target)
+}
+
+///remove meters of listener "addr" and its backends when it's shut down.
+pub(crate) fn remove_meters(addr: &SocketAddr) {
+    if let Ok(mut m) = meters().write() {
+        m.retain(|k, _| k.0 != addr.to_string());
+    }
+}
+
+///connect by "f", latency or failure is observed by "meters".
+pub(crate) async fn measure<T>(meters: &[&Meter], f: impl Future<Output = Option<T>>) -> Option<T> {
+    let t = Instant::now();
+    let o = f.await;
+    let d = t.elapsed();
+    for m in meters {
+        if o.is_some() {
+            m.observe(d);
+        } else {
+            m.fail();
+        }
+    }
+    o
+}
+
+//label value escapes '\', '"' and line feed.
+fn label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+///all meters in text format of Prometheus, like 'netproxy_listener_connections_total{listener="127.0.0.1:80"} 3'.
+pub(crate) fn metrics_text() -> String {
+    let map = if let Ok(m) = meters().read() {
+        m
+    } else {
+        return String::new();
+    };
+    let mut s = String::new();
+    for kind in ["listener", "backend"] {
+        let v: Vec<(String, &Meter)> = map
+            .iter()
+            .filter(|(k, _)| k.1.is_empty() == (kind == "listener"))
+            .map(|(k, m)| {
+                let mut l = format!("listener=\"{}\"", label(&k.0));
+                if !k.1.is_empty() {
+                    let _ = write!(l, ",backend=\"{}\"", label(&k.1));
+                }
+                (l, m.as_ref())
+            })
+            .collect();
+        if v.is_empty() {
+            continue;
+        }
+        for (i, (name, t, help)) in FAMILIES.iter().enumerate() {
+            let _ = writeln!(s, "# HELP netproxy_{kind}_{name} {help}");
+            let _ = writeln!(s, "# TYPE netproxy_{kind}_{name} {t}");
+            for (l, m) in &v {
+                let _ = writeln!(s, "netproxy_{kind}_{name}{{{l}}} {}", m.values()[i]);
+            }
+        }
+        let name = format!("netproxy_{kind}_connect_seconds");
+        let _ = writeln!(s, "# HELP {name} latency of connections to targets");
+        let _ = writeln!(s, "# TYPE {name} histogram");
+        for (l, m) in &v {
+            let mut n = 0;
+            for (b, c) in BUCKETS.iter().zip(&m.buckets) {
+                n += c.load(Ordering::Relaxed);
+                let _ = writeln!(s, "{name}_bucket{{{l},le=\"{b}\"}} {n}");
+            }
+            let count = m.latency_count.load(Ordering::Relaxed);
+            let sum = m.latency_sum.load(Ordering::Relaxed) as f64 / 1e6;
+            let _ = writeln!(s, "{name}_bucket{{{l},le=\"+Inf\"}} {count}");
+            let _ = writeln!(s, "{name}_sum{{{l}}} {sum}");
+            let _ = writeln!(s, "{name}_count{{{l}}} {count}");
+        }
+    }
+    s
+}
+
+#[test]
+fn test() {
+    let m = backend_meter("127.0.0.1:1", "127.0.0.1:2");
+    m.open();
+    m.add(10, 20);
+    m.observe(Duration::from_millis(3));
+    let (a, b) = Flow::pair(listener_meter("127.0.0.1:1"));
+    a.add(5);
+    b.add(7);
+    let (a, b) = Flow::backend_pair(m.clone());
+    a.add(3);
+    b.add(4);
+    let s = metrics_text();
+    assert!(s.contains("netproxy_listener_received_bytes_total{listener=\"127.0.0.1:1\"} 5"));
+    assert!(s.contains("netproxy_listener_sent_bytes_total{listener=\"127.0.0.1:1\"} 7"));
+    assert!(s.contains(
+        "netproxy_backend_sent_bytes_total{listener=\"127.0.0.1:1\",backend=\"127.0.0.1:2\"} 23"
+    ));
+    assert!(s.contains(
+        "netproxy_backend_received_bytes_total{listener=\"127.0.0.1:1\",backend=\"127.0.0.1:2\"} 14"
+    ));
+    assert!(s.contains(
+        "netproxy_backend_active_connections{listener=\"127.0.0.1:1\",backend=\"127.0.0.1:2\"} 1"
+    ));
+    assert!(s.contains(
+        "netproxy_backend_connect_seconds_bucket{listener=\"127.0.0.1:1\",backend=\"127.0.0.1:2\",le=\"0.005\"} 1"
+    ));
+    remove_meters(&"127.0.0.1:1".parse().unwrap());
+    //meters are global, other tests may have their own.
+    assert!(!metrics_text().contains("listener=\"127.0.0.1:1\""));
+}
@@ -1,5 +1,6 @@
 use super::*;
 use async_trait::async_trait;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -55,17 +56,60 @@ pub(crate) async fn read_loop<T, S>(
     }
 }
 
-async fn service_loop<T, S>(mut server: BufStream<T>, mut func: S)
+///the maximum length of a message.
+const MESSAGE_MAX: usize = 1 << 20;
+
+//a message at the start of "buf" is taken, none if it isn't complete.
+//error if its length is over MESSAGE_MAX.
+fn take_message(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>, usize> {
+    let n = if let Some(o) = buf.first_chunk::<4>() {
+        u32::from_be_bytes(*o) as usize
+    } else {
+        return Ok(None);
+    };
+    if n > MESSAGE_MAX {
+        return Err(n);
+    }
+    if buf.len() < 4 + n {
+        return Ok(None);
+    }
+    let v = buf[4..4 + n].to_vec();
+    buf.drain(..4 + n);
+    Ok(Some(v))
+}
+
+///a message with big-endian u32 length before it.
+pub(crate) fn to_message(mut v: Vec<u8>) -> Vec<u8> {
+    let mut o = (v.len() as u32).to_be_bytes().to_vec();
+    o.append(&mut v);
+    o
+}
+
+//every message is served in order, its reply is a message.
+async fn service_loop<T, S>(mut server: BufStream<T>, peer: SocketAddr, mut func: S)
 where
     T: AsyncReadExt + AsyncWriteExt + Unpin,
     S: FuncRw,
 {
     loop {
         server.read().await;
-        if server.has_remaining() {
-            let mut r_buf = server.take_buf();
-            func.service(&mut r_buf, server.w_buf_mut()).await;
-            server.write_buf().await;
+        loop {
+            match take_message(server.r_buf_mut()) {
+                Ok(Some(mut r_buf)) => {
+                    let mut w_buf = Vec::new();
+                    let open = func.service(peer, &mut r_buf, &mut w_buf).await;
+                    server.write(to_message(w_buf)).await;
+                    if !open {
+                        debug!("close");
+                        return;
+                    }
+                }
+                Ok(None) => break,
+                Err(n) => {
+                    error!("message length: {n}");
+                    return;
+                }
+            }
         }
         if server.r_f() || server.w_f() {
             debug!("end");
@@ -88,11 +132,12 @@ impl<T> FuncStream for ServiceTls<T>
 where
     T: FuncRw,
 {
-    async fn consume(self, server: TcpStream) {
+    async fn consume(self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("service tls start");
         if let Some(server) = tls_accept(&self.tls_acceptor, server).await {
-            service_loop(BufStream::new(server), self.func).await;
+            service_loop(BufStream::new(server), peer, self.func).await;
         }
+        (0, 0)
     }
 }
 
@@ -121,9 +166,10 @@ impl<T> FuncStream for Service<T>
 where
     T: FuncRw,
 {
-    async fn consume(mut self, server: TcpStream) {
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("service start");
-        service_loop(BufStream::new(server), self.func).await;
+        service_loop(BufStream::new(server), peer, self.func).await;
+        (0, 0)
     }
 }
 
@@ -135,3 +181,15 @@ where
         Self { func }
     }
 }
+
+#[test]
+fn test() {
+    let mut buf = to_message(b"state".to_vec());
+    buf.extend_from_slice(&to_message(b"a\nb".to_vec()));
+    buf.extend_from_slice(&[0, 0]);
+    assert_eq!(take_message(&mut buf), Ok(Some(b"state".to_vec())));
+    assert_eq!(take_message(&mut buf), Ok(Some(b"a\nb".to_vec())));
+    assert_eq!(take_message(&mut buf), Ok(None));
+    let mut buf = vec![0xff; 4];
+    assert!(take_message(&mut buf).is_err());
+}
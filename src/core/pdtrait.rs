@@ -1,13 +1,30 @@
+use super::{Flow, HttpHead};
 use async_trait::async_trait;
+use std::net::SocketAddr;
 
 #[async_trait]
 pub(crate) trait FuncR: Clone + Send + Sync + 'static {
     async fn data(&mut self, buf: &mut Vec<u8>);
 
     async fn enddata(&mut self, buf: &mut Vec<u8>);
+
+    ///bytes of data which are passed.
+    fn sum(&self) -> usize;
+
+    ///header of HTTP request or response, return true if it's edited.
+    ///"peer" is address of client, "tls" is true if client connects by TLS.
+    async fn head(&mut self, _head: &mut HttpHead, _peer: SocketAddr, _tls: bool) -> bool {
+        false
+    }
+
+    ///bytes are also added to "flow" of the backend which is connected.
+    fn set_backend(&mut self, _flow: Option<Flow>) {}
 }
 
 #[async_trait]
 pub(crate) trait FuncRw: Clone + Send + Sync + 'static {
-    async fn service(&mut self, r_buf: &mut Vec<u8>, w_buf: &mut Vec<u8>);
+    ///serve a message from "peer" and write its reply, messages are framed by big-endian u32 length.
+    ///return false if the connection is closed after the reply.
+    async fn service(&mut self, peer: SocketAddr, r_buf: &mut Vec<u8>, w_buf: &mut Vec<u8>)
+    -> bool;
 }
@@ -0,0 +1,319 @@
+use super::*;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, sleep};
+
+pub(crate) const V1: &str = "v1";
+pub(crate) const V2: &str = "v2";
+pub(crate) const ACCEPT_PROXY: &str = "accept_proxy";
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+///the maximum length of v1 header.
+const V1_MAX: usize = 107;
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+//version 2, command PROXY.
+const V2_PROXY: u8 = 0x21;
+const V2_LOCAL: u8 = 0x20;
+const V2_TCP4: u8 = 0x11;
+const V2_TCP6: u8 = 0x21;
+
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_CLIENT_SSL: u8 = 0x01;
+
+///the maximum length of v2 header.
+const V2_MAX: usize = 16 + 65535;
+
+///PROXY protocol version.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ProxyVersion {
+    V1,
+    V2,
+}
+
+impl fmt::Display for ProxyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::V1 => V1,
+            Self::V2 => V2,
+        })
+    }
+}
+
+impl TryFrom<&str> for ProxyVersion {
+    type Error = ();
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            V1 => Ok(Self::V1),
+            V2 => Ok(Self::V2),
+            _ => Err(()),
+        }
+    }
+}
+
+///addresses of a connection, "src" is the client, "dst" is the proxy.
+///"authority" is the server name of TLS which is terminated by proxy.
+#[derive(Debug)]
+pub(crate) struct ProxyHeader {
+    src: SocketAddr,
+    dst: SocketAddr,
+    tls: bool,
+    authority: Option<String>,
+}
+
+impl ProxyHeader {
+    pub(crate) fn new(src: SocketAddr, dst: SocketAddr) -> Self {
+        Self {
+            src,
+            dst,
+            tls: false,
+            authority: None,
+        }
+    }
+
+    pub(crate) fn set_tls(&mut self, authority: Option<String>) {
+        self.tls = true;
+        self.authority = authority;
+    }
+
+    ///header of a connection which proxy makes itself, like a health check.
+    ///v1 is "UNKNOWN", v2 is command LOCAL without addresses.
+    pub(crate) fn local(v: ProxyVersion) -> Vec<u8> {
+        match v {
+            ProxyVersion::V1 => b"PROXY UNKNOWN\r\n".to_vec(),
+            ProxyVersion::V2 => {
+                let mut v = V2_SIGNATURE.to_vec();
+                v.extend_from_slice(&[V2_LOCAL, 0, 0, 0]);
+                v
+            }
+        }
+    }
+
+    pub(crate) fn encode(&self, v: ProxyVersion) -> Vec<u8> {
+        match v {
+            ProxyVersion::V1 => self.v1(),
+            ProxyVersion::V2 => self.v2(),
+        }
+    }
+
+    //mixed families are sent as IPv6.
+    fn ips(&self) -> (IpAddr, IpAddr) {
+        match (self.src.ip(), self.dst.ip()) {
+            (a, b) if a.is_ipv4() != b.is_ipv4() => (to_v6(a), to_v6(b)),
+            o => o,
+        }
+    }
+
+    //"PROXY TCP4 src dst sport dport\r\n"
+    fn v1(&self) -> Vec<u8> {
+        let (a, b) = self.ips();
+        let f = if a.is_ipv4() { "TCP4" } else { "TCP6" };
+        format!(
+            "PROXY {f} {a} {b} {} {}\r\n",
+            self.src.port(),
+            self.dst.port()
+        )
+        .into_bytes()
+    }
+
+    fn v2(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let fam = match self.ips() {
+            (IpAddr::V4(a), IpAddr::V4(b)) => {
+                body.extend_from_slice(&a.octets());
+                body.extend_from_slice(&b.octets());
+                V2_TCP4
+            }
+            (a, b) => {
+                for ip in [a, b] {
+                    if let IpAddr::V6(o) = to_v6(ip) {
+                        body.extend_from_slice(&o.octets());
+                    }
+                }
+                V2_TCP6
+            }
+        };
+        body.extend_from_slice(&self.src.port().to_be_bytes());
+        body.extend_from_slice(&self.dst.port().to_be_bytes());
+        if let Some(s) = &self.authority {
+            tlv(&mut body, PP2_TYPE_AUTHORITY, s.as_bytes());
+        }
+        if self.tls {
+            //client, verify.
+            tlv(&mut body, PP2_TYPE_SSL, &[PP2_CLIENT_SSL, 0, 0, 0, 0]);
+        }
+
+        let mut v = V2_SIGNATURE.to_vec();
+        v.push(V2_PROXY);
+        v.push(fam);
+        v.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        v.extend(body);
+        v
+    }
+}
+
+///senders which are trusted to send PROXY protocol header, listener accepts header if it's not empty.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AcceptProxy(HashSet<IpAddr>);
+
+impl fmt::Display for AcceptProxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let mut v: Vec<String> = self.0.iter().map(|i| i.to_string()).collect();
+        v.sort();
+        write!(f, " {ACCEPT_PROXY}={}", v.join(","))
+    }
+}
+
+impl AcceptProxy {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn trust(&self, ip: IpAddr) -> bool {
+        self.0.contains(&ip)
+    }
+
+    ///"v" is ip list, split by ','.
+    pub(crate) fn set(&mut self, v: &str) -> bool {
+        let o: Result<HashSet<IpAddr>, _> = v.split(',').map(|s| s.parse()).collect();
+        o.map(|o| self.0 = o).is_ok()
+    }
+}
+
+fn to_v6(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(a) => IpAddr::V6(a.to_ipv6_mapped()),
+        o => o,
+    }
+}
+
+fn tlv(v: &mut Vec<u8>, t: u8, value: &[u8]) {
+    v.push(t);
+    v.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    v.extend_from_slice(value);
+}
+
+///parse PROXY protocol v1 or v2 header, return its length and source address.
+///source address is none for "UNKNOWN" or "LOCAL", IPv4-mapped address is IPv4.
+///"Ok(None)" if the header is incomplete.
+pub(crate) fn parse_proxy(buf: &[u8]) -> Result<Option<(usize, Option<SocketAddr>)>, ()> {
+    let n = buf.len();
+    if buf.first() == V1_PREFIX.first() {
+        if buf[..n.min(V1_PREFIX.len())] != V1_PREFIX[..n.min(V1_PREFIX.len())] {
+            return Err(());
+        }
+        return match buf.windows(2).take(V1_MAX - 1).position(|w| w == b"\r\n") {
+            Some(i) => parse_v1(&into_str(&buf[..i])).map(|o| Some((i + 2, o))),
+            None if n < V1_MAX => Ok(None),
+            None => Err(()),
+        };
+    }
+    if buf[..n.min(12)] != V2_SIGNATURE[..n.min(12)] {
+        return Err(());
+    }
+    if n < 16 {
+        return Ok(None);
+    }
+    let len = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if n < len {
+        return Ok(None);
+    }
+    let addrs = &buf[16..len];
+    match buf[12] {
+        V2_LOCAL => Ok(Some((len, None))),
+        V2_PROXY => Ok(Some((len, parse_v2(buf[13], addrs)))),
+        _ => Err(()),
+    }
+}
+
+//"PROXY TCP4 src dst sport dport"
+fn parse_v1(s: &str) -> Result<Option<SocketAddr>, ()> {
+    let v: Vec<&str> = s.split(' ').collect();
+    match v.get(1) {
+        Some(&"UNKNOWN") => Ok(None),
+        Some(&"TCP4") | Some(&"TCP6") if v.len() == 6 => {
+            let ip: IpAddr = v[2].parse().map_err(|_| ())?;
+            let port: u16 = v[4].parse().map_err(|_| ())?;
+            Ok(Some(SocketAddr::new(ip.to_canonical(), port)))
+        }
+        _ => Err(()),
+    }
+}
+
+//other families than TCP over IPv4 or IPv6 have no address.
+fn parse_v2(fam: u8, buf: &[u8]) -> Option<SocketAddr> {
+    let (ip, n) = match fam {
+        V2_TCP4 => {
+            let a: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(a)), 4)
+        }
+        V2_TCP6 => {
+            let a: [u8; 16] = buf.get(..16)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(a)), 16)
+        }
+        _ => return None,
+    };
+    let port = buf.get(n * 2..n * 2 + 2)?;
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    Some(SocketAddr::new(ip.to_canonical(), port))
+}
+
+///read PROXY protocol header from "s", return source address or "peer" if there is no address.
+///none if there is no valid header.
+pub(crate) async fn read_proxy(s: &mut TcpStream, peer: SocketAddr) -> Option<SocketAddr> {
+    let mut buf = vec![0; V2_MAX];
+    loop {
+        let n = s
+            .peek(&mut buf)
+            .await
+            .map_err(|e| error!("read proxy: {e}"))
+            .ok()?;
+        match parse_proxy(&buf[..n]) {
+            Ok(Some((len, addr))) => {
+                s.read_exact(&mut buf[..len])
+                    .await
+                    .map_err(|e| error!("read proxy: {e}"))
+                    .ok()?;
+                return Some(addr.unwrap_or(peer));
+            }
+            Ok(None) if n > 0 => sleep(Duration::from_millis(10)).await,
+            _ => return None,
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let a: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+    let b: SocketAddr = "[::1]:443".parse().unwrap();
+    let mut h = ProxyHeader::new(a, b);
+    assert_eq!(
+        h.encode(ProxyVersion::V1),
+        b"PROXY TCP6 ::ffff:10.0.0.1 ::1 5000 443\r\n"
+    );
+    h.set_tls(Some("a.com".to_string()));
+    let v = h.encode(ProxyVersion::V2);
+    let authority = [&[PP2_TYPE_AUTHORITY, 0, 5][..], b"a.com"].concat();
+    assert!(v.windows(authority.len()).any(|w| w == authority));
+    assert_eq!(v[12..14], [V2_PROXY, V2_TCP6]);
+    assert_eq!(u16::from_be_bytes([v[14], v[15]]) as usize, v.len() - 16);
+    assert!(v.ends_with(&[PP2_TYPE_SSL, 0, 5, PP2_CLIENT_SSL, 0, 0, 0, 0]));
+    assert_eq!(parse_proxy(&v), Ok(Some((v.len(), Some(a)))));
+    assert_eq!(parse_proxy(&v[..20]), Ok(None));
+    let v = ProxyHeader::new(a, "10.0.0.2:80".parse().unwrap()).encode(ProxyVersion::V1);
+    assert_eq!(parse_proxy(&v), Ok(Some((v.len(), Some(a)))));
+    assert_eq!(parse_proxy(b"PROXY UNKNOWN\r\n"), Ok(Some((15, None))));
+    for v in [ProxyVersion::V1, ProxyVersion::V2] {
+        let h = ProxyHeader::local(v);
+        assert_eq!(parse_proxy(&h), Ok(Some((h.len(), None))));
+    }
+    assert_eq!(parse_proxy(b"PRO"), Ok(None));
+    assert_eq!(parse_proxy(b"GET / HTTP/1.1\r\n"), Err(()));
+}
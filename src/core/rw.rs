@@ -1,8 +1,16 @@
 use super::*;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const CAPACITY: usize = 8192;
 
+///stream of TCP or TLS.
+pub(crate) trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T> AsyncStream for T where T: AsyncRead + AsyncWrite + Unpin + Send {}
+
+///stream to remote whatever its protocol is.
+pub(crate) type Upstream = BufStream<Box<dyn AsyncStream>>;
+
 #[derive(CopyGetters, MutGetters)]
 pub(crate) struct BufStream<T>
 where
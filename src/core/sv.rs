@@ -3,9 +3,10 @@ use async_trait::async_trait;
 use std::collections::HashSet;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{OnceCell, mpsc, oneshot};
-use tokio::time::{Duration, interval};
+use tokio::sync::{OnceCell, mpsc};
+use tokio::time::{Duration, interval, timeout};
 use tokio_native_tls::native_tls::{
     Identity, Protocol, TlsAcceptor as NativeAcceptor, TlsConnector as NativeConnector,
 };
@@ -13,8 +14,13 @@ use tokio_native_tls::{TlsAcceptor, TlsConnector, TlsStream};
 
 const CHANNEL_CAPACITY: usize = 1000;
 
+const CONTROL_CAPACITY: usize = 16;
+
 const CAPACITY: usize = 8192;
 
+///time to wait for PROXY protocol header.
+const PROXY_WAIT: Duration = Duration::from_secs(5);
+
 static TLS_CONNECTOR: OnceCell<TlsConnector> = OnceCell::const_new();
 
 async fn tls_connector() -> &'static TlsConnector {
@@ -58,36 +64,53 @@ pub(crate) enum ControlInfo {
     Close,
     IpScope(Vec<IpAddr>),
     Ip(IpAddr),
+    ///targets and proportion which replace targets of a route.
+    Update(Vec<String>, Vec<usize>),
 }
 
 pub(crate) enum StateInfo {
     Sum(u32, String),
+    Health(String, bool),
+    ///target which is removed by update, its health isn't shown.
+    Removed(String),
+    ///bytes from client and bytes to client of a connection which ends.
+    Bytes(usize, usize),
 }
 
 #[async_trait]
 pub(crate) trait FuncStream: Clone + Send + Sync + 'static {
-    async fn consume(self, socket: TcpStream);
+    ///"peer" is address of client, it's from PROXY protocol header if server accepts header.
+    ///return bytes from client and bytes to client.
+    async fn consume(self, socket: TcpStream, peer: SocketAddr) -> (usize, usize);
 }
 
-#[derive(Getters)]
+#[derive(Getters, Setters)]
 pub(crate) struct Server {
     listener: TcpListener,
     #[getset(get = "pub(crate)")]
     addr: SocketAddr,
-    control_receiver: oneshot::Receiver<ControlInfo>,
+    control_receiver: mpsc::Receiver<ControlInfo>,
+    #[getset(get = "pub(crate)")]
     state_sender: mpsc::Sender<StateInfo>,
     ip_scope: HashSet<IpAddr>,
+    #[getset(set = "pub(crate)")]
+    accept_proxy: AcceptProxy,
+    ///accepted connections and other served things in this second.
+    #[getset(get = "pub(crate)")]
+    counter: Arc<AtomicU32>,
+    ///connections are counted if there is a meter.
+    #[getset(set = "pub(crate)")]
+    meter: Option<Arc<Meter>>,
+    ///targets of ControlInfo::Update are sent to it, server can't be updated without it.
+    #[getset(set = "pub(crate)")]
+    update: Option<mpsc::Sender<(Vec<String>, Vec<usize>)>>,
 }
 
 impl Server {
     pub(crate) async fn new(
         s: &str,
-    ) -> Option<(
-        Self,
-        oneshot::Sender<ControlInfo>,
-        mpsc::Receiver<StateInfo>,
-    )> {
-        let (control_sender, control_receiver) = oneshot::channel();
+    ) -> Option<(Self, mpsc::Sender<ControlInfo>, mpsc::Receiver<StateInfo>)> {
+        let (control_sender, control_receiver) = mpsc::channel(CONTROL_CAPACITY);
         let (state_sender, state_receiver) = mpsc::channel(CHANNEL_CAPACITY);
 
         Self::with_channel(s, control_receiver, state_sender)
@@ -97,7 +120,7 @@ impl Server {
 
     pub(crate) async fn with_channel(
         s: &str,
-        control_receiver: oneshot::Receiver<ControlInfo>,
+        control_receiver: mpsc::Receiver<ControlInfo>,
         state_sender: mpsc::Sender<StateInfo>,
     ) -> Option<Self> {
         info!("server bind[{}]", s);
@@ -113,6 +136,10 @@ impl Server {
                 control_receiver,
                 state_sender,
                 ip_scope: HashSet::new(),
+                accept_proxy: AcceptProxy::default(),
+                counter: Arc::new(AtomicU32::new(0)),
+                meter: None,
+                update: None,
             })
             .map_err(|e| error!("server: {e}"))
             .ok()
@@ -130,17 +157,10 @@ impl Server {
     }
 
     fn reject_ip(&self, ip: IpAddr) -> bool {
-        if self.ip_scope.is_empty() {
-            return false;
-        }
-        if self.ip_scope.contains(&ip) {
-            return false;
-        }
-        true
+        reject_ip(&self.ip_scope, ip)
     }
 
     pub(crate) async fn accept(&mut self, func: impl FuncStream) {
-        let mut n: u32 = 0;
         let mut interval = interval(Duration::from_secs(1));
         loop {
             tokio::select! {
@@ -154,22 +174,43 @@ impl Server {
                     };
                     trace!("server accept");
 
-                    if let Ok(peer_addr) = socket.peer_addr() {
-                        let ip = peer_addr.ip();
-                        if self.reject_ip(ip) {
-                            info!("server reject ip: {ip}");
-                            drop(socket);
+                    let peer = if let Ok(o) = socket.peer_addr() {
+                        o
+                    } else {
+                        continue;
+                    };
+                    if !self.accept_proxy.is_empty() {
+                        if !self.accept_proxy.trust(peer.ip()) {
+                            info!("server reject sender: {}", peer.ip());
                             continue;
                         }
+                        let func = func.clone();
+                        let scope = self.ip_scope.clone();
+                        let meter = self.meter.clone();
+                        let state = self.state_sender.clone();
+                        tokio::spawn(async move {
+                            accept_proxy(socket, peer, scope, meter, state, func).await;
+                        });
+                        self.counter.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+
+                    let ip = peer.ip();
+                    if self.reject_ip(ip) {
+                        info!("server reject ip: {ip}");
+                        drop(socket);
+                        continue;
                     }
 
                     let func = func.clone();
+                    let meter = self.meter.clone();
+                    let state = self.state_sender.clone();
                     tokio::spawn(async move {
-                        func.consume(socket).await;
+                        consume(socket, peer, meter, state, func).await;
                     });
-                    n += 1;
+                    self.counter.fetch_add(1, Ordering::Relaxed);
                 },
-                Ok(c) = &mut self.control_receiver => {
+                Some(c) = self.control_receiver.recv() => {
                     match c{
                         ControlInfo::Close => {
                             info!("server {:?} stop", self.addr);
@@ -181,12 +222,20 @@ impl Server {
                         ControlInfo::Ip(i) => {
                             self.add_ip_scope(i);
                         },
+                        ControlInfo::Update(a, p) => {
+                            if let Some(u) = &self.update {
+                                let _ = u.send((a, p)).await;
+                            } else {
+                                warn!("server {:?} can't be updated", self.addr);
+                            }
+                        },
                     }
                 }
                 _ = interval.tick() => {
+                    let n = self.counter.load(Ordering::Relaxed);
                     if n > 0 {
-                        if let Ok(_) = self.state_sender.try_send(StateInfo::Sum(n,now_str())){
-                            n = 0;
+                        if self.state_sender.try_send(StateInfo::Sum(n,now_str())).is_ok() {
+                            self.counter.fetch_sub(n, Ordering::Relaxed);
                         }
                     }
                 }
@@ -195,6 +244,56 @@ impl Server {
     }
 }
 
+fn reject_ip(scope: &HashSet<IpAddr>, ip: IpAddr) -> bool {
+    !scope.is_empty() && !scope.contains(&ip)
+}
+
+//connection is active in "meter" until it's consumed, then its bytes are sent to state.
+async fn consume(
+    socket: TcpStream,
+    peer: SocketAddr,
+    meter: Option<Arc<Meter>>,
+    state: mpsc::Sender<StateInfo>,
+    func: impl FuncStream,
+) {
+    if let Some(m) = &meter {
+        m.open();
+    }
+    let (up, down) = func.consume(socket, peer).await;
+    if let Some(m) = &meter {
+        m.close();
+    }
+    trace!("server[{peer}] end, up:{up} down:{down}");
+    if up > 0 || down > 0 {
+        let _ = state.send(StateInfo::Bytes(up, down)).await;
+    }
+}
+
+//read PROXY protocol header from a trusted sender, then ip scope applies to address of client.
+async fn accept_proxy(
+    mut socket: TcpStream,
+    sender: SocketAddr,
+    scope: HashSet<IpAddr>,
+    meter: Option<Arc<Meter>>,
+    state: mpsc::Sender<StateInfo>,
+    func: impl FuncStream,
+) {
+    let peer = match timeout(PROXY_WAIT, read_proxy(&mut socket, sender)).await {
+        Ok(Some(o)) => o,
+        _ => {
+            info!("server reject sender: {} without PROXY header", sender.ip());
+            return;
+        }
+    };
+    let ip = peer.ip();
+    if reject_ip(&scope, ip) {
+        info!("server reject ip: {ip} from {}", sender.ip());
+        return;
+    }
+    debug!("server accept[{peer}] from {sender}");
+    consume(socket, peer, meter, state, func).await;
+}
+
 async fn connect(str: &str) -> Option<TcpStream> {
     TcpStream::connect(str)
         .await
@@ -260,4 +359,17 @@ impl Client {
         }
         connect_tls(t, h).await.map(|stream| BufStream::new(stream))
     }
+
+    ///stream to remote, TCP if its protocol is plain, otherwise TLS.
+    pub(crate) async fn stream(&mut self) -> Option<Upstream> {
+        let t = self.remote.target();
+        match self.remote.protoc() {
+            Protoc::TCP | Protoc::HTTPPT | Protoc::SNI => connect(t)
+                .await
+                .map(|s| BufStream::new(Box::new(s) as Box<dyn AsyncStream>)),
+            _ => connect_tls(t, self.remote.host())
+                .await
+                .map(|s| BufStream::new(Box::new(s) as Box<dyn AsyncStream>)),
+        }
+    }
 }
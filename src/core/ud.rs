@@ -0,0 +1,261 @@
+use super::*;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::{UdpSocket, lookup_host};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, interval, sleep};
+
+const CHANNEL_CAPACITY: usize = 1000;
+
+const CONTROL_CAPACITY: usize = 16;
+
+const SESSION_CAPACITY: usize = 100;
+
+const DATAGRAM_CAPACITY: usize = 65535;
+
+///a session without any datagram in this time is expired.
+const SESSION_IDLE: Duration = Duration::from_secs(60);
+
+#[async_trait]
+pub(crate) trait FuncRemote: Clone + Send + Sync + 'static {
+    ///choose a remote for session from "peer".
+    async fn remote(&mut self, peer: SocketAddr) -> Remote;
+
+    ///session to remote ends.
+    async fn done(&mut self, remote: &Remote);
+}
+
+#[derive(Getters, Setters)]
+pub(crate) struct UdpServer {
+    socket: Arc<UdpSocket>,
+    #[getset(get = "pub(crate)")]
+    addr: SocketAddr,
+    control_receiver: mpsc::Receiver<ControlInfo>,
+    #[getset(get = "pub(crate)")]
+    state_sender: mpsc::Sender<StateInfo>,
+    ip_scope: HashSet<IpAddr>,
+    sessions: HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>,
+    ///sessions and datagrams are counted if there is a meter.
+    #[getset(set = "pub(crate)")]
+    meter: Option<Arc<Meter>>,
+    ///targets of ControlInfo::Update are sent to it, server can't be updated without it.
+    #[getset(set = "pub(crate)")]
+    update: Option<mpsc::Sender<(Vec<String>, Vec<usize>)>>,
+}
+
+impl UdpServer {
+    pub(crate) async fn new(
+        s: &str,
+    ) -> Option<(Self, mpsc::Sender<ControlInfo>, mpsc::Receiver<StateInfo>)> {
+        let (control_sender, control_receiver) = mpsc::channel(CONTROL_CAPACITY);
+        let (state_sender, state_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+        info!("udp server bind[{}]", s);
+        let socket = UdpSocket::bind(s)
+            .await
+            .map_err(|e| error!("udp server: {e}"))
+            .ok()?;
+        socket
+            .local_addr()
+            .map(|addr| Self {
+                socket: Arc::new(socket),
+                addr,
+                control_receiver,
+                state_sender,
+                ip_scope: HashSet::new(),
+                sessions: HashMap::new(),
+                meter: None,
+                update: None,
+            })
+            .map(|o| (o, control_sender, state_receiver))
+            .map_err(|e| error!("udp server: {e}"))
+            .ok()
+    }
+
+    pub(crate) fn set_ip_scope(&mut self, ipscope: &Vec<IpAddr>) {
+        self.ip_scope.clear();
+        for i in ipscope {
+            self.ip_scope.insert(*i);
+        }
+    }
+
+    fn reject_ip(&self, ip: IpAddr) -> bool {
+        !self.ip_scope.is_empty() && !self.ip_scope.contains(&ip)
+    }
+
+    ///receive datagrams, every client address has its own session and upstream socket.
+    pub(crate) async fn relay(&mut self, func: impl FuncRemote) {
+        let mut n: u32 = 0;
+        let mut interval = interval(Duration::from_secs(1));
+        let mut buf = vec![0; DATAGRAM_CAPACITY];
+        loop {
+            tokio::select! {
+                r = self.socket.recv_from(&mut buf) => {
+                    let (len, peer) = match r {
+                        Ok(o) => o,
+                        Err(e) => {
+                            error!("udp server recv: {e}");
+                            continue;
+                        }
+                    };
+                    if self.reject_ip(peer.ip()) {
+                        info!("udp server reject ip: {}", peer.ip());
+                        continue;
+                    }
+
+                    if let Some(m) = &self.meter {
+                        m.add(len, 0);
+                    }
+                    let mut data = buf[..len].to_vec();
+                    if let Some(s) = self.sessions.get(&peer) {
+                        match s.try_send(data) {
+                            Ok(_) => continue,
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                debug!("udp session[{peer}] full, drop datagram");
+                                continue;
+                            }
+                            Err(mpsc::error::TrySendError::Closed(d)) => {
+                                self.sessions.remove(&peer);
+                                data = d;
+                            }
+                        }
+                    }
+
+                    let meter = self.meter.clone();
+                    let state = self.state_sender.clone();
+                    let s = udp_session(self.socket.clone(), peer, meter, state, func.clone());
+                    let _ = s.try_send(data);
+                    self.sessions.insert(peer, s);
+                    n += 1;
+                },
+                Some(c) = self.control_receiver.recv() => {
+                    match c {
+                        ControlInfo::Close => {
+                            info!("udp server {:?} stop", self.addr);
+                            return;
+                        },
+                        ControlInfo::IpScope(o) => {
+                            self.set_ip_scope(&o)
+                        },
+                        ControlInfo::Ip(i) => {
+                            self.ip_scope.insert(i);
+                        },
+                        ControlInfo::Update(a, p) => {
+                            if let Some(u) = &self.update {
+                                let _ = u.send((a, p)).await;
+                            } else {
+                                warn!("udp server {:?} can't be updated", self.addr);
+                            }
+                        },
+                    }
+                }
+                _ = interval.tick() => {
+                    self.sessions.retain(|_, s| !s.is_closed());
+                    if n > 0 {
+                        if self.state_sender.try_send(StateInfo::Sum(n, now_str())).is_ok() {
+                            n = 0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn udp_connect(s: &str) -> Option<UdpSocket> {
+    let addr = lookup_host(s)
+        .await
+        .map_err(|e| error!("udp connect: {e}"))
+        .ok()?
+        .next()?;
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .map_err(|e| error!("udp connect: {e}"))
+        .ok()?;
+    socket
+        .connect(addr)
+        .await
+        .map_err(|e| error!("udp connect: {e}"))
+        .ok()?;
+    Some(socket)
+}
+
+///relay datagrams between client and upstream until the session is idle, then its bytes are sent to state.
+///bytes to client are added to meter as they pass.
+///remote is chosen and connected in the session task, datagrams wait in the channel until then.
+fn udp_session(
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    meter: Option<Arc<Meter>>,
+    state: mpsc::Sender<StateInfo>,
+    mut func: impl FuncRemote,
+) -> mpsc::Sender<Vec<u8>> {
+    let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(SESSION_CAPACITY);
+    tokio::spawn(async move {
+        let remote = func.remote(peer).await;
+        debug!("udp session[{peer}]-[{}]", remote.target());
+        let upstream = if let Some(o) = udp_connect(remote.target()).await {
+            o
+        } else {
+            func.done(&remote).await;
+            return;
+        };
+        if let Some(m) = &meter {
+            m.open();
+        }
+        let mut buf = vec![0; DATAGRAM_CAPACITY];
+        let (mut up, mut down) = (0, 0);
+        loop {
+            tokio::select! {
+                o = receiver.recv() => {
+                    if let Some(o) = o {
+                        if let Err(e) = upstream.send(&o).await {
+                            error!("udp session send: {e}");
+                            break;
+                        }
+                        up += o.len();
+                    } else {
+                        break;
+                    }
+                }
+                r = upstream.recv(&mut buf) => {
+                    match r {
+                        Ok(n) => {
+                            if let Err(e) = socket.send_to(&buf[..n], peer).await {
+                                error!("udp session send_to: {e}");
+                                break;
+                            }
+                            if let Some(m) = &meter {
+                                m.add(0, n);
+                            }
+                            down += n;
+                        }
+                        Err(e) => {
+                            error!("udp session recv: {e}");
+                            break;
+                        }
+                    }
+                }
+                _ = sleep(SESSION_IDLE) => {
+                    debug!("udp session[{peer}] expired");
+                    break;
+                }
+            }
+        }
+        if let Some(m) = &meter {
+            m.close();
+        }
+        if up > 0 || down > 0 {
+            let _ = state.send(StateInfo::Bytes(up, down)).await;
+        }
+        func.done(&remote).await;
+    });
+    sender
+}
@@ -1,8 +1,11 @@
 #![allow(dead_code)]
 
 mod args;
+mod audit;
+mod auth;
 mod builder;
 mod core;
+mod metrics;
 mod route;
 mod state;
 mod visit;
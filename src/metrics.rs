@@ -0,0 +1,61 @@
+use crate::core::*;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+
+const PATH: &str = "/metrics";
+
+const NOT_FOUND: &[u8] = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
+///serve meters by "GET /metrics" for Prometheus.
+#[derive(Clone, Debug)]
+struct Metrics;
+
+#[async_trait]
+impl FuncStream for Metrics {
+    async fn consume(self, socket: TcpStream, peer: SocketAddr) -> (usize, usize) {
+        trace!("metrics[{peer}]");
+        let mut s = BufStream::new(socket);
+        loop {
+            read_head(&mut s).await;
+            let (head, n) = if let Some(o) = HttpHead::parse(s.r_buf_mut()) {
+                o
+            } else {
+                return (0, 0);
+            };
+            s.r_buf_mut().drain(..n);
+            s.write(response(&head)).await;
+            if s.w_f() || s.r_f() {
+                return (0, 0);
+            }
+        }
+    }
+}
+
+fn response(head: &HttpHead) -> Vec<u8> {
+    let method = head.first();
+    let path = head.second().split('?').next().unwrap_or_default();
+    if path != PATH || (method != "GET" && method != "HEAD") {
+        return NOT_FOUND.to_vec();
+    }
+    let body = metrics_text();
+    let mut v = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    if method == "GET" {
+        v.extend_from_slice(body.as_bytes());
+    }
+    v
+}
+
+///serve meters at "addr", it isn't a server of configuration and can't be shut down.
+pub(crate) fn start_up(addr: String) {
+    new_thread_tokiort_block_on(async move {
+        if let Some((mut server, _c, _s)) = Server::new(&addr).await {
+            info!("metrics[{}]", server.addr());
+            server.accept(Metrics).await;
+        }
+    });
+}
@@ -0,0 +1,508 @@
+use super::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BALANCE: &str = "balance";
+const RR: &str = "rr";
+const RANDOM: &str = "random";
+const LEASTCONN: &str = "leastconn";
+const P2C: &str = "p2c";
+const IPHASH: &str = "iphash";
+const CHASH: &str = "chash";
+
+///virtual nodes of a target on hash ring for every unit of proportion.
+const VIRTUAL_NODES: usize = 40;
+
+///strategy to choose a target.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) enum Balance {
+    #[default]
+    RoundRobin,
+    Random,
+    LeastConn,
+    TwoChoice,
+    IpHash,
+    HashRing,
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::RoundRobin => return Ok(()),
+            Self::Random => RANDOM,
+            Self::LeastConn => LEASTCONN,
+            Self::TwoChoice => P2C,
+            Self::IpHash => IPHASH,
+            Self::HashRing => CHASH,
+        };
+        write!(f, " {BALANCE}={s}")
+    }
+}
+
+impl Balance {
+    pub(super) fn is_option(k: &str) -> bool {
+        k == BALANCE
+    }
+
+    pub(super) fn set(&mut self, v: &str) -> bool {
+        *self = match v {
+            RR => Self::RoundRobin,
+            RANDOM => Self::Random,
+            LEASTCONN => Self::LeastConn,
+            P2C => Self::TwoChoice,
+            IPHASH => Self::IpHash,
+            CHASH => Self::HashRing,
+            _ => return false,
+        };
+        true
+    }
+
+    ///finder of "addrs" with "proportion", other settings are from "r".
+    pub(super) fn finder(
+        &self,
+        r: &RouteInfo,
+        addrs: Vec<String>,
+        proportion: Vec<usize>,
+    ) -> RouteFinder {
+        RouteFinder::new(self.alg(Targets::new(addrs, proportion)), r)
+    }
+
+    ///algorithm of "addrs" with "proportion", health and connections in flight are from "old".
+    pub(super) fn renew(
+        &self,
+        addrs: Vec<String>,
+        proportion: Vec<usize>,
+        old: &dyn FuncRouteAlg,
+    ) -> Box<dyn FuncRouteAlg> {
+        let mut t = Targets::new(addrs, proportion);
+        for (i, s) in t.addrs.iter().enumerate() {
+            if let Some((up, n)) = old.target(s) {
+                t.health[i] = up;
+                t.active[i] = n;
+            }
+        }
+        self.alg(t)
+    }
+
+    fn alg(&self, t: Targets) -> Box<dyn FuncRouteAlg> {
+        match self {
+            Self::RoundRobin => Box::new(RouteAlg::new(t)),
+            Self::Random => Box::new(RandomAlg::new(t)),
+            Self::LeastConn => Box::new(LeastConnAlg(t)),
+            Self::TwoChoice => Box::new(TwoChoiceAlg::new(t)),
+            Self::IpHash => Box::new(IpHashAlg::new(t)),
+            Self::HashRing => Box::new(HashRingAlg::new(t)),
+        }
+    }
+}
+
+//targets with proportion, health and connections in flight.
+struct Targets {
+    addrs: Vec<String>,
+    proportion: Vec<usize>,
+    health: Vec<bool>,
+    active: Vec<usize>,
+}
+
+impl Targets {
+    fn new(addrs: Vec<String>, proportion: Vec<usize>) -> Self {
+        let health = vec![true; addrs.len()];
+        let active = vec![0; addrs.len()];
+        Self {
+            addrs,
+            proportion,
+            health,
+            active,
+        }
+    }
+
+    //proportion of healthy targets, if no target is healthy, use all.
+    fn weights(&self) -> Vec<usize> {
+        let w: Vec<usize> = self
+            .proportion
+            .iter()
+            .zip(&self.health)
+            .map(|(p, h)| if *h { *p } else { 0 })
+            .collect();
+        if w.iter().any(|n| *n > 0) {
+            w
+        } else if self.proportion.iter().any(|n| *n > 0) {
+            warn!("no healthy target");
+            self.proportion.clone()
+        } else {
+            vec![1; self.addrs.len()]
+        }
+    }
+
+    //weights without targets in "exclude", like targets which fail for a connection.
+    //if every target is excluded, weights of all.
+    fn weights_except(&self, exclude: &[String]) -> Vec<usize> {
+        let w = self.weights();
+        if exclude.is_empty() {
+            return w;
+        }
+        let keep: Vec<bool> = self.addrs.iter().map(|s| !exclude.contains(s)).collect();
+        let except = |w: &[usize]| -> Vec<usize> {
+            w.iter()
+                .zip(&keep)
+                .map(|(n, k)| if *k { *n } else { 0 })
+                .collect()
+        };
+        //healthy targets, then unhealthy targets, then targets without proportion.
+        [
+            except(&w),
+            except(&self.proportion),
+            except(&vec![1; w.len()]),
+        ]
+        .into_iter()
+        .find(|o| o.iter().any(|n| *n > 0))
+        .unwrap_or(w)
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        if let Some(i) = self.addrs.iter().position(|s| s == target) {
+            self.health[i] = up;
+        }
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        let i = self.addrs.iter().position(|s| s == target)?;
+        Some((self.health[i], self.active[i]))
+    }
+
+    fn get(&mut self, i: usize) -> (String, String) {
+        self.active[i] += 1;
+        let s = self.addrs[i].clone();
+        let h = host_of(&s).to_string();
+        (s, h)
+    }
+
+    fn done(&mut self, target: &str) {
+        if let Some(i) = self.addrs.iter().position(|s| s == target) {
+            self.active[i] = self.active[i].saturating_sub(1);
+        }
+    }
+
+    //"i" has less connections than "j" in proportion.
+    fn less(&self, w: &[usize], i: usize, j: usize) -> bool {
+        self.active[i] * w[j] < self.active[j] * w[i]
+    }
+}
+
+fn hash(o: impl Hash) -> u64 {
+    let mut h = DefaultHasher::new();
+    o.hash(&mut h);
+    h.finish()
+}
+
+//xorshift, it's enough to spread connections.
+struct Rand(u64);
+
+impl Rand {
+    fn new() -> Self {
+        let n = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self(n | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    //choose an index by weights.
+    fn weighted(&mut self, w: &[usize]) -> usize {
+        let sum: usize = w.iter().sum();
+        let mut n = (self.next() % sum as u64) as usize;
+        for (i, o) in w.iter().enumerate() {
+            if n < *o {
+                return i;
+            }
+            n -= o;
+        }
+        0
+    }
+}
+
+//weighted round-robin.
+struct RouteAlg {
+    t: Targets,
+    n: usize,
+    index: Vec<usize>,
+}
+
+impl FuncRouteAlg for RouteAlg {
+    fn addr(&mut self, _: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        for _ in 0..self.index.len() {
+            let i = self.index[self.n];
+            self.n = (self.n + 1) % self.index.len();
+            if !exclude.contains(&self.t.addrs[i]) {
+                return self.t.get(i);
+            }
+        }
+        //targets in index are all excluded.
+        let index = get_index(self.t.weights_except(exclude));
+        self.t.get(index[0])
+    }
+
+    fn done(&mut self, target: &str) {
+        self.t.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.t.set_health(target, up);
+        self.index = get_index(self.t.weights());
+        self.n = 0;
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.t.target(target)
+    }
+}
+
+impl RouteAlg {
+    fn new(t: Targets) -> Self {
+        let index = get_index(t.weights());
+        Self { t, n: 0, index }
+    }
+}
+
+//weighted random.
+struct RandomAlg {
+    t: Targets,
+    rand: Rand,
+    weights: Vec<usize>,
+}
+
+impl FuncRouteAlg for RandomAlg {
+    fn addr(&mut self, _: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        let i = if exclude.is_empty() {
+            self.rand.weighted(&self.weights)
+        } else {
+            self.rand.weighted(&self.t.weights_except(exclude))
+        };
+        self.t.get(i)
+    }
+
+    fn done(&mut self, target: &str) {
+        self.t.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.t.set_health(target, up);
+        self.weights = self.t.weights();
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.t.target(target)
+    }
+}
+
+impl RandomAlg {
+    fn new(t: Targets) -> Self {
+        let weights = t.weights();
+        Self {
+            t,
+            rand: Rand::new(),
+            weights,
+        }
+    }
+}
+
+//least connections in proportion.
+struct LeastConnAlg(Targets);
+
+impl FuncRouteAlg for LeastConnAlg {
+    fn addr(&mut self, _: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        let w = self.0.weights_except(exclude);
+        let mut i = w.iter().position(|n| *n > 0).unwrap_or_default();
+        for j in i + 1..w.len() {
+            if w[j] > 0 && self.0.less(&w, j, i) {
+                i = j;
+            }
+        }
+        self.0.get(i)
+    }
+
+    fn done(&mut self, target: &str) {
+        self.0.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.0.set_health(target, up);
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.0.target(target)
+    }
+}
+
+//power of two choices, choose two targets by weighted random and use the less loaded.
+struct TwoChoiceAlg {
+    t: Targets,
+    rand: Rand,
+}
+
+impl FuncRouteAlg for TwoChoiceAlg {
+    fn addr(&mut self, _: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        let w = self.t.weights_except(exclude);
+        let a = self.rand.weighted(&w);
+        let b = self.rand.weighted(&w);
+        let i = if self.t.less(&w, b, a) { b } else { a };
+        self.t.get(i)
+    }
+
+    fn done(&mut self, target: &str) {
+        self.t.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.t.set_health(target, up);
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.t.target(target)
+    }
+}
+
+impl TwoChoiceAlg {
+    fn new(t: Targets) -> Self {
+        Self {
+            t,
+            rand: Rand::new(),
+        }
+    }
+}
+
+//source ip hash over weighted index.
+struct IpHashAlg {
+    t: Targets,
+    index: Vec<usize>,
+}
+
+impl FuncRouteAlg for IpHashAlg {
+    fn addr(&mut self, peer: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        let n = hash(peer) % self.index.len() as u64;
+        let i = self.index[n as usize];
+        if exclude.contains(&self.t.addrs[i]) {
+            //the hash of peer chooses among targets which are left.
+            let index = get_index(self.t.weights_except(exclude));
+            return self
+                .t
+                .get(index[(hash(peer) % index.len() as u64) as usize]);
+        }
+        self.t.get(i)
+    }
+
+    fn done(&mut self, target: &str) {
+        self.t.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.t.set_health(target, up);
+        self.index = get_index(self.t.weights());
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.t.target(target)
+    }
+}
+
+impl IpHashAlg {
+    fn new(t: Targets) -> Self {
+        let index = get_index(t.weights());
+        Self { t, index }
+    }
+}
+
+//consistent hashing of source ip, a ring of virtual nodes.
+//an unhealthy target only moves its own clients.
+struct HashRingAlg {
+    t: Targets,
+    ring: Vec<(u64, usize)>,
+}
+
+impl FuncRouteAlg for HashRingAlg {
+    fn addr(&mut self, peer: Option<IpAddr>, exclude: &[String]) -> (String, String) {
+        let w = self.t.weights_except(exclude);
+        let h = hash(peer);
+        let n = self.ring.partition_point(|o| o.0 < h);
+        let i = (0..self.ring.len())
+            .map(|k| self.ring[(n + k) % self.ring.len()].1)
+            .find(|i| w[*i] > 0)
+            .unwrap_or_default();
+        self.t.get(i)
+    }
+
+    fn done(&mut self, target: &str) {
+        self.t.done(target);
+    }
+
+    fn set_health(&mut self, target: &str, up: bool) {
+        self.t.set_health(target, up);
+    }
+
+    fn target(&self, target: &str) -> Option<(bool, usize)> {
+        self.t.target(target)
+    }
+}
+
+impl HashRingAlg {
+    fn new(t: Targets) -> Self {
+        let mut ring = Vec::new();
+        for (i, s) in t.addrs.iter().enumerate() {
+            for k in 0..t.proportion[i].max(1) * VIRTUAL_NODES {
+                ring.push((hash((s, k)), i));
+            }
+        }
+        ring.sort_unstable();
+        Self { t, ring }
+    }
+}
+
+#[test]
+fn test() {
+    let addrs: Vec<String> = (1..4).map(|n| format!("127.0.0.1:{n}")).collect();
+
+    let mut a = LeastConnAlg(Targets::new(addrs.clone(), vec![1, 1, 1]));
+    assert_eq!(a.addr(None, &[]).0, addrs[0]);
+    assert_eq!(a.addr(None, &[]).0, addrs[1]);
+    a.done(&addrs[0]);
+    assert_eq!(a.addr(None, &[]).0, addrs[0]);
+    let mut a = LeastConnAlg(Targets::new(vec!["[::1]:80".to_string()], vec![1]));
+    assert_eq!(
+        a.addr(None, &[]),
+        ("[::1]:80".to_string(), "::1".to_string())
+    );
+
+    let mut a = HashRingAlg::new(Targets::new(addrs.clone(), vec![1, 1, 1]));
+    let peer = "10.0.0.1".parse().ok();
+    let s = a.addr(peer, &[]).0;
+    assert_eq!(a.addr(peer, &[]).0, s);
+    a.set_health(&s, false);
+    assert_ne!(a.addr(peer, &[]).0, s);
+
+    assert_eq!(a.addr(peer, &addrs[..2]).0, addrs[2]);
+
+    let b = Balance::RoundRobin.renew(addrs.clone(), vec![2, 1, 1], &a);
+    assert_eq!(b.target(&s), Some((false, 2)));
+    let b = Balance::RoundRobin.renew(vec!["127.0.0.1:9".to_string()], vec![1], &a);
+    assert_eq!(b.target(&s), None);
+
+    //a target which fails isn't chosen again for the connection.
+    let mut a = IpHashAlg::new(Targets::new(addrs.clone(), vec![1, 1, 1]));
+    let s = a.addr(peer, &[]).0;
+    let t = a.addr(peer, std::slice::from_ref(&s)).0;
+    assert_ne!(t, s);
+    let u = a.addr(peer, &[s.clone(), t.clone()]).0;
+    assert!(u != s && u != t);
+    let mut a = RouteAlg::new(Targets::new(addrs.clone(), vec![1, 1, 1]));
+    assert_eq!(a.addr(None, &[]).0, addrs[0]);
+    assert_eq!(a.addr(None, &addrs[1..2]).0, addrs[2]);
+}
@@ -0,0 +1,333 @@
+use super::*;
+use std::net::IpAddr;
+
+const X_FORWARDED: &str = "x_forwarded";
+const FORWARDED: &str = "forwarded";
+
+const REQUEST: &str = "request";
+const RESPONSE: &str = "response";
+const SET: &str = "set";
+const REPLACE: &str = "replace";
+const REMOVE: &str = "remove";
+
+///headers which frame messages and keep connections, they can't be edited.
+const FRAMING: [&str; 3] = ["content-length", "transfer-encoding", "connection"];
+
+///what of client is forwarded, its address, scheme and "Host" header of request.
+const PARTS: [&str; 3] = ["for", "proto", "host"];
+
+///an edit of header, name and value are split by ':'.
+#[derive(Clone, Debug)]
+enum Edit {
+    ///set a header, replace headers of the same name if there are.
+    Set(String, String),
+    ///replace headers of the same name if there are.
+    Replace(String, String),
+    Remove(String),
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Set(k, v) => write!(f, "{SET}={k}:{}", escape(v)),
+            Self::Replace(k, v) => write!(f, "{REPLACE}={k}:{}", escape(v)),
+            Self::Remove(k) => write!(f, "{REMOVE}={k}"),
+        }
+    }
+}
+
+impl Edit {
+    //whitespace of value is written as '\s'.
+    fn new(op: &str, v: &str) -> Option<Self> {
+        let v = v.replace("\\s", " ");
+        if op == REMOVE {
+            return is_name(&v).then_some(Self::Remove(v));
+        }
+        let (k, v) = v.split_once(':')?;
+        if !is_name(k) {
+            return None;
+        }
+        let (k, v) = (k.to_string(), v.trim().to_string());
+        match op {
+            SET => Some(Self::Set(k, v)),
+            REPLACE => Some(Self::Replace(k, v)),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, head: &mut HttpHead) {
+        match self {
+            Self::Set(k, v) => head.set_header(k, v),
+            Self::Replace(k, v) => {
+                if head.header(k).is_some() {
+                    head.set_header(k, v);
+                }
+            }
+            Self::Remove(k) => head.remove_header(k),
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace(' ', "\\s")
+}
+
+//"token" of RFC 9110.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+//name of a header which can be edited.
+fn is_name(s: &str) -> bool {
+    is_token(s) && !FRAMING.contains(&s.to_ascii_lowercase().as_str())
+}
+
+//value of "Forwarded" is quoted if it isn't a token.
+fn quote(s: &str) -> String {
+    if is_token(s) {
+        s.to_string()
+    } else {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+///"X-Forwarded-*" and "Forwarded" headers which are added to requests, and edits of request and response headers.
+///like 'x_forwarded=for,proto,host forwarded=for request_set=X-A:1 request_remove=Cookie response_replace=Server:netproxy'.
+#[derive(Clone, Debug, Default)]
+pub(super) struct HeaderRule {
+    x_forwarded: Vec<String>,
+    forwarded: Vec<String>,
+    request: Vec<Edit>,
+    response: Vec<Edit>,
+}
+
+impl fmt::Display for HeaderRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.x_forwarded.is_empty() {
+            write!(f, " {X_FORWARDED}={}", self.x_forwarded.join(","))?;
+        }
+        if !self.forwarded.is_empty() {
+            write!(f, " {FORWARDED}={}", self.forwarded.join(","))?;
+        }
+        for o in &self.request {
+            write!(f, " {REQUEST}_{o}")?;
+        }
+        for o in &self.response {
+            write!(f, " {RESPONSE}_{o}")?;
+        }
+        Ok(())
+    }
+}
+
+impl HeaderRule {
+    pub(super) fn is_option(k: &str) -> bool {
+        k == X_FORWARDED
+            || k == FORWARDED
+            || k.split_once('_').is_some_and(|(a, b)| {
+                [REQUEST, RESPONSE].contains(&a) && [SET, REPLACE, REMOVE].contains(&b)
+            })
+    }
+
+    pub(super) fn set(&mut self, k: &str, v: &str) -> bool {
+        if k == X_FORWARDED || k == FORWARDED {
+            let parts: Vec<String> = v.split(',').map(|s| s.to_ascii_lowercase()).collect();
+            if !parts.iter().all(|s| PARTS.contains(&s.as_str())) {
+                return false;
+            }
+            if k == X_FORWARDED {
+                self.x_forwarded = parts;
+            } else {
+                self.forwarded = parts;
+            }
+            return true;
+        }
+        let (side, op) = if let Some(o) = k.split_once('_') {
+            o
+        } else {
+            return false;
+        };
+        let edit = if let Some(o) = Edit::new(op, v) {
+            o
+        } else {
+            return false;
+        };
+        match side {
+            REQUEST => self.request.push(edit),
+            RESPONSE => self.response.push(edit),
+            _ => return false,
+        }
+        true
+    }
+
+    //"X-Forwarded-For" and "Forwarded" are appended to those of former proxies.
+    //headers of former proxies are joined in order into one header.
+    fn request(&self, head: &mut HttpHead, peer: SocketAddr, tls: bool) -> bool {
+        let ip = peer.ip();
+        let proto = if tls { "https" } else { "http" };
+        let host = head.header("Host").map(|s| s.to_string());
+        for p in &self.x_forwarded {
+            match p.as_str() {
+                "for" => {
+                    let mut v = head.headers("X-Forwarded-For");
+                    let ip = ip.to_string();
+                    v.push(&ip);
+                    let v = v.join(", ");
+                    head.set_header("X-Forwarded-For", &v);
+                }
+                "proto" => head.set_header("X-Forwarded-Proto", proto),
+                _ => {
+                    if let Some(h) = &host {
+                        head.set_header("X-Forwarded-Host", h);
+                    }
+                }
+            }
+        }
+        if !self.forwarded.is_empty() {
+            let v: Vec<String> = self
+                .forwarded
+                .iter()
+                .filter_map(|p| match p.as_str() {
+                    "for" => Some(format!("for={}", node(ip))),
+                    "proto" => Some(format!("proto={proto}")),
+                    _ => host.as_ref().map(|h| format!("host={}", quote(h))),
+                })
+                .collect();
+            let v = v.join(";");
+            let mut o = head.headers("Forwarded");
+            o.push(&v);
+            let v = o.join(", ");
+            head.set_header("Forwarded", &v);
+        }
+        self.request.iter().for_each(|o| o.apply(head));
+        !self.x_forwarded.is_empty() || !self.forwarded.is_empty() || !self.request.is_empty()
+    }
+
+    fn response(&self, head: &mut HttpHead) -> bool {
+        self.response.iter().for_each(|o| o.apply(head));
+        !self.response.is_empty()
+    }
+}
+
+//IPv6 node is quoted with brackets.
+fn node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(o) => o.to_string(),
+        IpAddr::V6(o) => format!("\"[{o}]\""),
+    }
+}
+
+///data func of http routes, it counts bytes like "RouteR" and edits headers by rule.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpR {
+    sum: usize,
+    flow: Flow,
+    backend: Option<Flow>,
+    rule: Arc<HeaderRule>,
+    response: bool,
+}
+
+#[async_trait]
+impl FuncR for HttpR {
+    async fn data(&mut self, buf: &mut Vec<u8>) {
+        self.count(buf.len());
+    }
+
+    async fn enddata(&mut self, buf: &mut Vec<u8>) {
+        self.count(buf.len());
+    }
+
+    fn sum(&self) -> usize {
+        self.sum
+    }
+
+    async fn head(&mut self, head: &mut HttpHead, peer: SocketAddr, tls: bool) -> bool {
+        if self.response {
+            self.rule.response(head)
+        } else {
+            self.rule.request(head, peer, tls)
+        }
+    }
+
+    fn set_backend(&mut self, flow: Option<Flow>) {
+        self.backend = flow;
+    }
+}
+
+impl HttpR {
+    ///func of requests, and func of responses on listener "addr".
+    pub(super) fn pair(rule: HeaderRule, addr: &str) -> (Self, Self) {
+        let rule = Arc::new(rule);
+        let (a, b) = Flow::pair(listener_meter(addr));
+        let o = Self {
+            sum: 0,
+            flow: a,
+            backend: None,
+            rule,
+            response: false,
+        };
+        let mut r = o.clone();
+        r.flow = b;
+        r.response = true;
+        (o, r)
+    }
+
+    fn count(&mut self, n: usize) {
+        self.sum += n;
+        self.flow.add(n);
+        if let Some(f) = &self.backend {
+            f.add(n);
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let mut r = HeaderRule::default();
+    assert!(r.set(X_FORWARDED, "for,proto,host"));
+    assert!(r.set(FORWARDED, "for,host"));
+    assert!(!r.set(FORWARDED, "by"));
+    assert!(r.set("request_set", "X-A:a\\sb"));
+    assert!(r.set("request_remove", "Cookie"));
+    assert!(r.set("response_replace", "Server:x"));
+    assert!(!r.set("request_set", "X A:1"));
+    assert!(!r.set("request_set", "Content-Length:0"));
+    assert!(!r.set("response_remove", "Transfer-Encoding"));
+    assert!(!r.set("response_replace", "connection:close"));
+    assert!(!HeaderRule::is_option("request_add"));
+    assert_eq!(
+        r.to_string(),
+        " x_forwarded=for,proto,host forwarded=for,host request_set=X-A:a\\sb request_remove=Cookie response_replace=Server:x"
+    );
+
+    let buf =
+        b"GET / HTTP/1.1\r\nHost: a.com:8080\r\nCookie: c\r\nX-Forwarded-For: 10.0.0.1\r\n\r\n";
+    let (mut head, _) = HttpHead::parse(buf).unwrap();
+    assert!(r.request(&mut head, "[::1]:1000".parse().unwrap(), true));
+    assert_eq!(head.header("X-Forwarded-For"), Some("10.0.0.1, ::1"));
+    assert_eq!(head.header("X-Forwarded-Proto"), Some("https"));
+    assert_eq!(head.header("X-Forwarded-Host"), Some("a.com:8080"));
+    assert_eq!(
+        head.header("Forwarded"),
+        Some("for=\"[::1]\";host=\"a.com:8080\"")
+    );
+    assert_eq!(head.header("x-a"), Some("a b"));
+    assert_eq!(head.header("Cookie"), None);
+
+    let buf = b"GET / HTTP/1.1\r\nX-Forwarded-For: 10.0.0.1\r\nForwarded: for=10.0.0.1\r\nX-Forwarded-For: 10.0.0.2, 10.0.0.3\r\nForwarded: for=10.0.0.2\r\n\r\n";
+    let (mut head, _) = HttpHead::parse(buf).unwrap();
+    assert!(r.request(&mut head, "10.0.0.4:1000".parse().unwrap(), false));
+    assert_eq!(
+        head.headers("X-Forwarded-For"),
+        ["10.0.0.1, 10.0.0.2, 10.0.0.3, 10.0.0.4"]
+    );
+    assert_eq!(
+        head.headers("Forwarded"),
+        ["for=10.0.0.1, for=10.0.0.2, for=10.0.0.4"]
+    );
+
+    let (mut head, _) = HttpHead::parse(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+    assert!(r.response(&mut head));
+    assert_eq!(head.header("Server"), None);
+}
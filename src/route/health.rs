@@ -0,0 +1,237 @@
+use super::*;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tokio::time::{Duration, interval, timeout};
+
+const CHECK: &str = "check";
+const CHECK_PATH: &str = "check_path";
+const CHECK_STATUS: &str = "check_status";
+const CHECK_INTERVAL: &str = "check_interval";
+const RISE: &str = "rise";
+const FALL: &str = "fall";
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CheckKind {
+    Tcp,
+    Http,
+}
+
+///periodic check of every route target.
+///"rise" successes put an unhealthy target back, "fall" failures take a healthy target out.
+#[derive(Clone, Debug)]
+pub(crate) struct HealthCheck {
+    kind: Option<CheckKind>,
+    path: String,
+    status: u16,
+    interval: u64,
+    rise: u32,
+    fall: u32,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            path: "/".to_string(),
+            status: 200,
+            interval: 5,
+            rise: 2,
+            fall: 3,
+        }
+    }
+}
+
+impl fmt::Display for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            Some(CheckKind::Tcp) => write!(f, " {CHECK}={TCP}")?,
+            Some(CheckKind::Http) => write!(
+                f,
+                " {CHECK}={HTTP} {CHECK_PATH}={} {CHECK_STATUS}={}",
+                self.path, self.status
+            )?,
+            None => return Ok(()),
+        }
+        write!(
+            f,
+            " {CHECK_INTERVAL}={} {RISE}={} {FALL}={}",
+            self.interval, self.rise, self.fall
+        )
+    }
+}
+
+impl HealthCheck {
+    pub(super) fn is_option(k: &str) -> bool {
+        [CHECK, CHECK_PATH, CHECK_STATUS, CHECK_INTERVAL, RISE, FALL].contains(&k)
+    }
+
+    pub(super) fn set(&mut self, k: &str, v: &str) -> bool {
+        match k {
+            CHECK => match v {
+                TCP => self.kind = Some(CheckKind::Tcp),
+                HTTP => self.kind = Some(CheckKind::Http),
+                _ => return false,
+            },
+            CHECK_PATH => {
+                if !v.starts_with('/') {
+                    return false;
+                }
+                self.path = v.to_string();
+            }
+            CHECK_STATUS => return v.parse().map(|n| self.status = n).is_ok(),
+            CHECK_INTERVAL => {
+                return v
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .map(|n| self.interval = n)
+                    .is_some();
+            }
+            RISE => {
+                return v
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .map(|n| self.rise = n)
+                    .is_some();
+            }
+            FALL => {
+                return v
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .map(|n| self.fall = n)
+                    .is_some();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    ///run a check per target, take target out of (or put it back to) "finder".
+    ///return checks by target, a check runs until it's aborted.
+    pub(super) fn start(
+        &self,
+        protoc: Protoc,
+        targets: &[String],
+        finder: &RouteFinder,
+        state: &mpsc::Sender<StateInfo>,
+    ) -> HashMap<String, AbortHandle> {
+        if self.kind.is_none() {
+            return HashMap::new();
+        }
+        targets
+            .iter()
+            .map(|t| {
+                let c = self.clone();
+                let target = t.clone();
+                let finder = finder.clone();
+                let state = state.clone();
+                let h = tokio::spawn(async move { c.run(protoc, target, finder, state).await });
+                (t.clone(), h.abort_handle())
+            })
+            .collect()
+    }
+
+    async fn run(
+        self,
+        protoc: Protoc,
+        target: String,
+        finder: RouteFinder,
+        state: mpsc::Sender<StateInfo>,
+    ) {
+        //target which expects PROXY protocol header gets a header of local connection.
+        let head = finder.proxy.map(ProxyHeader::local).unwrap_or_default();
+        let mut up = true;
+        let mut n = 0;
+        let _ = state.send(StateInfo::Health(target.clone(), up)).await;
+        let mut interval = interval(Duration::from_secs(self.interval));
+        loop {
+            interval.tick().await;
+            let o = timeout(CHECK_TIMEOUT, self.check(protoc, &target, &head))
+                .await
+                .unwrap_or(false);
+            trace!("health check[{target}]: {o}");
+            if o == up {
+                n = 0;
+                continue;
+            }
+            n += 1;
+            if n < if up { self.fall } else { self.rise } {
+                continue;
+            }
+            n = 0;
+            up = o;
+            info!("health check[{target}]: {}", if up { "up" } else { "down" });
+            finder.alg.write().await.set_health(&target, up);
+            if state
+                .send(StateInfo::Health(target.clone(), up))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    async fn check(&self, protoc: Protoc, target: &str, head: &[u8]) -> bool {
+        let host = host_of(target).to_string();
+        let mut client = Client::new(Remote::new(protoc, target.to_string(), host.clone()));
+        if protoc == Protoc::TLS || protoc == Protoc::HTTP {
+            match client.tls_stream().await {
+                Some(s) => self.probe(s, &host, head).await,
+                None => false,
+            }
+        } else {
+            match client.tcp_stream().await {
+                Some(s) => self.probe(s, &host, head).await,
+                None => false,
+            }
+        }
+    }
+
+    //a connected stream passes tcp check, http check expects the status of "GET path".
+    //PROXY protocol "head" is sent first.
+    async fn probe<T>(&self, mut s: BufStream<T>, host: &str, head: &[u8]) -> bool
+    where
+        T: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        if !head.is_empty() {
+            s.write(head.to_vec()).await;
+            if s.w_f() {
+                return false;
+            }
+        }
+        if self.kind != Some(CheckKind::Http) {
+            return true;
+        }
+        //an IPv6 address is in brackets.
+        let host = if host.contains(':') {
+            format!("[{host}]")
+        } else {
+            host.to_string()
+        };
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.path, host
+        );
+        s.write(req.into_bytes()).await;
+        if s.w_f() {
+            return false;
+        }
+        loop {
+            s.read().await;
+            let buf = s.r_buf_mut();
+            if let Some(i) = buf.windows(2).position(|w| w == b"\r\n") {
+                let line = into_str(&buf[..i]);
+                return line.split_whitespace().nth(1) == Some(&self.status.to_string());
+            }
+            if s.r_f() {
+                return false;
+            }
+        }
+    }
+}
@@ -1,13 +1,138 @@
 use super::*;
 
-struct RouteAlg(Vec<String>, usize, Vec<usize>);
+//a pool is chosen for every request by its path, method and "Host" header.
+#[derive(Clone)]
+struct RouteHttp<T>
+where
+    T: FuncR,
+{
+    pools: PoolFinder,
+    server_data_func: T,
+    remote_data_func: T,
+    tls_acceptor: Option<Arc<TlsAcceptor>>,
+}
+
+#[async_trait]
+impl<T> FuncStream for RouteHttp<T>
+where
+    T: FuncR,
+{
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
+        trace!("route http start");
+
+        let tls = self.tls_acceptor.is_some();
+        let head = proxy_header(self.pools.default(), &server, peer, tls).await;
+        if let Some(t) = &self.tls_acceptor {
+            if let Some(s) = tls_accept(t, server).await {
+                self.vhost(&mut BufStream::new(s), peer, tls, head).await;
+            }
+        } else {
+            self.vhost(&mut BufStream::new(server), peer, tls, head)
+                .await;
+        }
+        (self.server_data_func.sum(), self.remote_data_func.sum())
+    }
+}
+
+impl<T> RouteHttp<T>
+where
+    T: FuncR,
+{
+    fn new(
+        pools: PoolFinder,
+        server_data_func: T,
+        remote_data_func: T,
+        t: Option<TlsAcceptor>,
+    ) -> Self {
+        Self {
+            pools,
+            server_data_func,
+            remote_data_func,
+            tls_acceptor: t.map(Arc::new),
+        }
+    }
 
-impl FuncRouteAlg for RouteAlg {
-    fn addr(&mut self) -> (String, String) {
-        let s = self.0[self.2[self.1]].clone();
-        self.1 = (self.1 + 1) % self.2.len();
-        let h = s.split(':').next().unwrap_or_default().to_string();
-        (s, h)
+    async fn vhost<S>(
+        &mut self,
+        server: &mut BufStream<S>,
+        peer: SocketAddr,
+        tls: bool,
+        head: Vec<u8>,
+    ) where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        let mut o = PoolHttp {
+            pools: &self.pools,
+            peer,
+            head,
+            chosen: None,
+            connected: None,
+        };
+        http_loop(
+            server,
+            peer,
+            tls,
+            &mut o,
+            &mut self.server_data_func,
+            &mut self.remote_data_func,
+        )
+        .await;
+    }
+}
+
+//pools of requests on a connection, PROXY protocol "head" is sent to every target connected.
+struct PoolHttp<'a> {
+    pools: &'a PoolFinder,
+    peer: SocketAddr,
+    head: Vec<u8>,
+    chosen: Option<RouteFinder>,
+    connected: Option<RouteFinder>,
+}
+
+#[async_trait]
+impl FuncHttp for PoolHttp<'_> {
+    async fn choose(&mut self, buf: &mut Vec<u8>) -> Option<String> {
+        let (host, method, path) = if let Ok(mut o) = HttpRequest::parse(buf) {
+            (o.get_host(), o.method(), o.path())
+        } else {
+            Default::default()
+        };
+        debug!("route http: {method} {host} {path}");
+        let (key, finder, path) = self.pools.request(host_of(&host), &method, &path);
+        if let Some(path) = path {
+            rewrite(buf, path);
+        }
+        self.chosen = Some(finder);
+        Some(key)
+    }
+
+    async fn connect(&mut self) -> Option<(String, Upstream)> {
+        let mut finder = self.chosen.clone()?;
+        let o = connect(&mut finder, self.peer, &self.head).await;
+        self.connected = Some(finder);
+        o
+    }
+
+    fn backend(&self, target: &str) -> Option<Arc<Meter>> {
+        self.connected.as_ref().map(|f| f.backend(target))
+    }
+
+    async fn done(&mut self, target: &str) {
+        if let Some(f) = &mut self.connected {
+            f.closed(target);
+            f.done(target).await;
+        }
+    }
+}
+
+//request line uses new path.
+fn rewrite(buf: &mut Vec<u8>, path: String) {
+    if let Some((mut head, n)) = HttpHead::parse(buf) {
+        trace!("rewrite path: {path}");
+        head.set_second(path);
+        let mut v = head.to_bytes();
+        v.extend_from_slice(&buf[n..]);
+        *buf = v;
     }
 }
 
@@ -15,25 +140,31 @@ pub(super) async fn http(r: RouteInfo) {
     debug!("server http start up");
 
     if let Some(t) = get_tls_acceptor().await {
-        let ra = RouteAlg(r.remote_addrs, 0, get_index(r.proportion));
-        let o = RouteTls::new(
-            RouteFinder::new(ra, r.remote_protoc),
-            RouteR::new(),
-            RouteR::new(),
-            t,
-        );
-        server_accept(&r.server_addr, o).await;
+        route_accept(r, |r| {
+            let pools = PoolFinder::new(r);
+            let (f1, f2) = HttpR::pair(r.headers.clone(), &r.listener);
+            let o = RouteHttp::new(pools.clone(), f1, f2, Some(t));
+            (pools, o)
+        })
+        .await;
     }
 }
 
 pub(super) async fn http_pt(r: RouteInfo) {
     debug!("server http_pt start up");
 
-    let ra = RouteAlg(r.remote_addrs, 0, get_index(r.proportion));
-    let o = Route::new(
-        RouteFinder::new(ra, r.remote_protoc),
-        RouteR::new(),
-        RouteR::new(),
-    );
-    server_accept(&r.server_addr, o).await;
+    route_accept(r, |r| {
+        let pools = PoolFinder::new(r);
+        let (f1, f2) = HttpR::pair(r.headers.clone(), &r.listener);
+        let o = RouteHttp::new(pools.clone(), f1, f2, None);
+        (pools, o)
+    })
+    .await;
+}
+
+#[test]
+fn test() {
+    let mut buf = b"GET /api/a HTTP/1.1\r\nHost: [::1]:80\r\n\r\nbody".to_vec();
+    rewrite(&mut buf, "/a".to_string());
+    assert_eq!(buf, b"GET /a HTTP/1.1\r\nHost: [::1]:80\r\n\r\nbody");
 }
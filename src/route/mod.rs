@@ -1,42 +1,192 @@
+mod alg;
+mod header;
+mod health;
 mod http;
+mod pool;
+mod sni;
+mod udp;
+
+use alg::Balance;
+use header::{HeaderRule, HttpR};
+use health::HealthCheck;
+use pool::{ALPN, HOST, PATH, Pool, PoolFinder, REGEX};
 
 use crate::core::*;
 use crate::state::*;
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, mpsc};
+use tokio::task::AbortHandle;
+use tokio::time::{Duration, Instant, timeout_at};
 use tokio_native_tls::TlsAcceptor;
 
 pub(crate) trait FuncRouteAlg: Send + Sync + 'static {
-    fn addr(&mut self) -> (String, String);
+    ///choose a target for connection from "peer", targets in "exclude" are chosen only if all are.
+    fn addr(&mut self, peer: Option<IpAddr>, exclude: &[String]) -> (String, String);
+
+    ///connection to target ends.
+    fn done(&mut self, target: &str);
+
+    fn set_health(&mut self, target: &str, up: bool);
+
+    ///health and connections in flight of "target", none if it isn't a target.
+    fn target(&self, target: &str) -> Option<(bool, usize)>;
+}
+
+const ATTEMPTS: &str = "attempts";
+const DEADLINE: &str = "deadline";
+const PROXY: &str = "proxy";
+
+const UPDATE_CAPACITY: usize = 16;
+
+///time to wait for ClientHello to get server name.
+const SNI_WAIT: Duration = Duration::from_secs(3);
+
+///connect to next target when a connection fails, up to "attempts" targets within "deadline" seconds.
+#[derive(Clone, Copy, Debug)]
+struct Failover {
+    attempts: u32,
+    deadline: Option<u64>,
+}
+
+impl Default for Failover {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            deadline: None,
+        }
+    }
+}
+
+impl fmt::Display for Failover {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.attempts > 1 {
+            write!(f, " {ATTEMPTS}={}", self.attempts)?;
+        }
+        if let Some(d) = self.deadline {
+            write!(f, " {DEADLINE}={d}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Failover {
+    fn set(&mut self, k: &str, v: &str) -> bool {
+        let n = if let Some(n) = v.parse().ok().filter(|n| *n > 0) {
+            n
+        } else {
+            return false;
+        };
+        match k {
+            ATTEMPTS => self.attempts = n as u32,
+            DEADLINE => self.deadline = Some(n),
+            _ => return false,
+        }
+        true
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+            .map(|d| Instant::now() + Duration::from_secs(d))
+    }
+}
+
+//wait for "f" until "deadline" if there is one.
+async fn within<T>(deadline: Option<Instant>, f: impl Future<Output = Option<T>>) -> Option<T> {
+    if let Some(d) = deadline {
+        timeout_at(d, f).await.ok().flatten()
+    } else {
+        f.await
+    }
 }
 
+///targets of a route or pool, and how they are connected.
 #[derive(Clone)]
-struct RouteFinder(Arc<RwLock<dyn FuncRouteAlg>>, Protoc);
+struct RouteFinder {
+    alg: Arc<RwLock<Box<dyn FuncRouteAlg>>>,
+    protoc: Protoc,
+    failover: Failover,
+    proxy: Option<ProxyVersion>,
+    ///address of listener, meters of targets are by it.
+    listener: Arc<str>,
+    ///makes a new algorithm when targets are updated.
+    balance: Balance,
+}
 
 impl RouteFinder {
-    fn new(a: impl FuncRouteAlg, p: Protoc) -> Self {
-        Self(Arc::new(RwLock::new(a)), p)
+    ///settings other than "a" are from "r".
+    fn new(a: Box<dyn FuncRouteAlg>, r: &RouteInfo) -> Self {
+        Self {
+            alg: Arc::new(RwLock::new(a)),
+            protoc: r.remote_protoc,
+            failover: r.failover,
+            proxy: r.proxy,
+            listener: r.listener.as_str().into(),
+            balance: r.balance,
+        }
     }
 
-    async fn get(&mut self) -> Remote {
-        let mut lock = self.0.write().await;
-        let (addr, h) = lock.addr();
+    ///replace the algorithm with one of "addrs", connections to targets go on.
+    ///return targets which are added.
+    async fn update(&self, addrs: Vec<String>, proportion: Vec<usize>) -> Vec<String> {
+        let mut lock = self.alg.write().await;
+        let added = addrs
+            .iter()
+            .filter(|s| lock.target(s).is_none())
+            .cloned()
+            .collect();
+        *lock = self.balance.renew(addrs, proportion, lock.as_ref());
+        added
+    }
+
+    ///targets in "exclude" are chosen only if all are.
+    async fn get(&mut self, peer: Option<SocketAddr>, exclude: &[String]) -> Remote {
+        let mut lock = self.alg.write().await;
+        let (addr, h) = lock.addr(peer.map(|o| o.ip()), exclude);
         drop(lock);
         trace!("RouteFinder get:({:?})", addr);
-        Remote::new(self.1.clone(), addr, h)
+        Remote::new(self.protoc, addr, h)
+    }
+
+    async fn done(&mut self, target: &str) {
+        self.alg.write().await.done(target);
+    }
+
+    fn backend(&self, target: &str) -> Arc<Meter> {
+        backend_meter(&self.listener, target)
+    }
+
+    ///connection to "target" is closed, its bytes are added as they pass.
+    fn closed(&self, target: &str) {
+        if let Some(m) = find_backend_meter(&self.listener, target) {
+            m.close();
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Getters)]
 pub(crate) struct RouteInfo {
     server_protoc: Protoc,
+    #[getset(get = "pub(crate)")]
     server_addr: String,
+    ///address which listener is bound to, meters are by it.
+    listener: String,
     remote_protoc: Protoc,
     remote_addrs: Vec<String>,
     proportion: Vec<usize>,
+    check: HealthCheck,
+    failover: Failover,
+    balance: Balance,
+    proxy: Option<ProxyVersion>,
+    accept_proxy: AcceptProxy,
+    headers: HeaderRule,
+    pools: Vec<Pool>,
 }
 
 impl RouteInfo {
@@ -49,14 +199,125 @@ impl RouteInfo {
     ) -> Self {
         Self {
             server_protoc,
+            listener: server_addr.clone(),
             server_addr,
             remote_protoc,
             remote_addrs,
             proportion,
+            check: HealthCheck::default(),
+            failover: Failover::default(),
+            balance: Balance::default(),
+            proxy: None,
+            accept_proxy: AcceptProxy::default(),
+            headers: HeaderRule::default(),
+            pools: Vec::new(),
+        }
+    }
+
+    ///replace targets and proportion, like "update" sentence does.
+    pub(crate) fn set_targets(&mut self, addrs: Vec<String>, proportion: Vec<usize>) {
+        self.remote_addrs = addrs;
+        self.proportion = proportion;
+    }
+
+    ///route with host, alpn, path or regex pools can't be updated.
+    pub(crate) fn has_pools(&self) -> bool {
+        !self.pools.is_empty()
+    }
+
+    ///set an option like "key=value", return false if it's invalid.
+    pub(crate) fn set_option(&mut self, k: &str, v: &str) -> bool {
+        if HealthCheck::is_option(k) {
+            //udp target can't be checked.
+            self.remote_protoc != Protoc::UDP && self.check.set(k, v)
+        } else if Balance::is_option(k) {
+            self.balance.set(v)
+        } else if let Some(kind) = [HOST, ALPN, PATH, REGEX].into_iter().find(|o| *o == k) {
+            //host and alpn pools of sni mode, host, path and regex pools of http.
+            let http = self.server_protoc == Protoc::HTTP || self.server_protoc == Protoc::HTTPPT;
+            let sni = self.server_protoc == Protoc::SNI;
+            let ok = match kind {
+                HOST => sni || http,
+                ALPN => sni,
+                _ => http,
+            };
+            ok && Pool::new(kind, v).map(|o| self.pools.push(o)).is_some()
+        } else if Pool::is_modifier(k) {
+            self.pools.last_mut().is_some_and(|o| o.set(k, v))
+        } else if HeaderRule::is_option(k) {
+            //headers are edited by http routes.
+            (self.server_protoc == Protoc::HTTP || self.server_protoc == Protoc::HTTPPT)
+                && self.headers.set(k, v)
+        } else if k == ACCEPT_PROXY {
+            self.server_protoc != Protoc::UDP && self.accept_proxy.set(v)
+        } else if k == PROXY {
+            //PROXY protocol is for stream.
+            self.remote_protoc != Protoc::UDP
+                && ProxyVersion::try_from(v)
+                    .map(|o| self.proxy = Some(o))
+                    .is_ok()
+        } else {
+            self.failover.set(k, v)
+        }
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p: Vec<String> = self.proportion.iter().map(|n| n.to_string()).collect();
+        write!(
+            f,
+            "{}-{} {} {} {}",
+            self.server_protoc,
+            self.remote_protoc,
+            self.server_addr,
+            self.remote_addrs.join(","),
+            p.join(":")
+        )?;
+        self.check.fmt(f)?;
+        self.failover.fmt(f)?;
+        self.balance.fmt(f)?;
+        if let Some(v) = self.proxy {
+            write!(f, " {PROXY}={v}")?;
+        }
+        self.accept_proxy.fmt(f)?;
+        self.headers.fmt(f)?;
+        for o in &self.pools {
+            o.fmt(f)?;
         }
+        Ok(())
     }
 }
 
+///target socket addr, one or several, split by ','.
+///proportion, split by ':'. if it's not digit, replace with 0. if number is less than socket addrs, fill with 1. it can be omitted.
+pub(crate) fn some_addr_proportion(a: &str, p: Option<&str>) -> (Vec<String>, Vec<usize>) {
+    let addr: Vec<String> = a.split(',').map(|s| s.to_string()).collect();
+    let addr_len = addr.len();
+
+    let proportion: Vec<usize> = if let Some(p) = p {
+        let mut p: Vec<usize> = p.split(':').map(|s| s.parse().unwrap_or(0)).collect();
+        if p.len() < addr_len {
+            p.resize(addr_len, 1);
+        } else if p.len() > addr_len {
+            p.truncate(addr_len);
+        }
+        p
+    } else {
+        vec![1; addr_len]
+    };
+    (addr, proportion)
+}
+
+///a target which appears more than once, targets of a pool must be distinct.
+pub(crate) fn duplicate(addrs: &[String]) -> Option<&String> {
+    addrs
+        .iter()
+        .enumerate()
+        .find(|(i, s)| addrs[..*i].contains(s))
+        .map(|o| o.1)
+}
+
 fn get_index(mut v: Vec<usize>) -> Vec<usize> {
     let mut p = Vec::<(usize, usize)>::new();
     for i in 0..v.len() {
@@ -80,72 +341,146 @@ fn get_index(mut v: Vec<usize>) -> Vec<usize> {
     v
 }
 
-struct RouteAlg(Vec<String>, usize, Vec<usize>);
-
-impl FuncRouteAlg for RouteAlg {
-    fn addr(&mut self) -> (String, String) {
-        let s = self.0[self.2[self.1]].clone();
-        self.1 = (self.1 + 1) % self.2.len();
-        let h = s.split(':').next().unwrap_or_default().to_string();
-        (s, h)
-    }
-}
-
-#[derive(Clone, Debug, CopyGetters)]
+///bytes are counted, and added to meters of listener and backend.
+#[derive(Clone, Debug)]
 pub(crate) struct RouteR {
-    #[getset(get_copy = "pub(crate)")]
     sum: usize,
+    flow: Flow,
+    backend: Option<Flow>,
 }
 
 #[async_trait]
 impl FuncR for RouteR {
     async fn data(&mut self, buf: &mut Vec<u8>) {
-        self.sum += buf.len();
+        self.count(buf.len());
     }
 
     async fn enddata(&mut self, buf: &mut Vec<u8>) {
-        self.sum += buf.len();
+        self.count(buf.len());
+    }
+
+    fn sum(&self) -> usize {
+        self.sum
+    }
+
+    fn set_backend(&mut self, flow: Option<Flow>) {
+        self.backend = flow;
     }
 }
 
 impl RouteR {
-    fn new() -> Self {
-        Self { sum: 0 }
+    ///func of data from clients, and func of data to clients on listener "addr".
+    fn pair(addr: &str) -> (Self, Self) {
+        let (a, b) = Flow::pair(listener_meter(addr));
+        let o = |flow| Self {
+            sum: 0,
+            flow,
+            backend: None,
+        };
+        (o(a), o(b))
     }
+
+    fn count(&mut self, n: usize) {
+        self.sum += n;
+        self.flow.add(n);
+        if let Some(f) = &self.backend {
+            f.add(n);
+        }
+    }
+}
+
+//PROXY protocol header of connection "s" from "peer" if route sends it, server name is in v2 header if TLS is terminated.
+async fn proxy_header(route: &RouteFinder, s: &TcpStream, peer: SocketAddr, tls: bool) -> Vec<u8> {
+    let v = if let Some(v) = route.proxy {
+        v
+    } else {
+        return Vec::new();
+    };
+    let mut h = if let Ok(o) = s.local_addr() {
+        ProxyHeader::new(peer, o)
+    } else {
+        return Vec::new();
+    };
+    if tls {
+        //TLS is terminated without ALPN negotiation, so no protocol is sent.
+        let hello = if v == ProxyVersion::V2 {
+            ClientHello::peek(s, SNI_WAIT).await
+        } else {
+            None
+        };
+        h.set_tls(hello.and_then(|o| o.sni().clone()));
+    }
+    h.encode(v)
+}
+
+//connect to a target of "route", next target is tried when connecting fails.
+//a target which fails isn't tried again, it stops when every target is tried.
+//PROXY protocol "head" is sent first.
+async fn connect(
+    route: &mut RouteFinder,
+    peer: SocketAddr,
+    head: &[u8],
+) -> Option<(String, Upstream)> {
+    let deadline = route.failover.deadline();
+    let mut tried = Vec::new();
+    for n in 1..=route.failover.attempts {
+        let remote = route.get(Some(peer), &tried).await;
+        let target = remote.target().clone();
+        if tried.contains(&target) {
+            route.done(&target).await;
+            warn!("every target failed");
+            return None;
+        }
+        debug!("remote[{:?}]", target);
+
+        let mut client = Client::new(remote);
+        let meter = route.backend(&target);
+        let meters = [meter.as_ref(), &listener_meter(&route.listener)];
+        if let Some(mut remote) = measure(&meters, within(deadline, client.stream())).await {
+            meter.open();
+            if !head.is_empty() {
+                remote.write(head.to_vec()).await;
+            }
+            return Some((target, remote));
+        }
+
+        route.done(&target).await;
+        warn!("remote[{target}] failed, attempt {n}");
+        tried.push(target);
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            warn!("failover deadline");
+            return None;
+        }
+    }
+    None
 }
 
 #[inline]
 async fn route<T>(
     server: &mut BufStream<T>,
+    peer: SocketAddr,
+    head: Vec<u8>,
     route: &mut RouteFinder,
     server_data_func: &mut impl FuncR,
     remote_data_func: &mut impl FuncR,
 ) where
     T: AsyncReadExt + AsyncWriteExt + Unpin,
 {
-    server.read().await;
+    if server.is_empty() {
+        server.read().await;
+    }
     if server.is_empty() {
         return;
     }
 
-    let remote = route.get().await;
-    let remote_protoc = remote.protoc();
-    debug!("remote[{:?}]", remote.target());
-
-    let mut client = Client::new(remote);
-
-    if remote_protoc == Protoc::TCP || remote_protoc == Protoc::HTTPPT {
-        trace!("to_tcp");
-
-        if let Some(mut remote) = client.tcp_stream().await {
-            read_loop(server, &mut remote, server_data_func, remote_data_func).await;
-        }
-    } else {
-        trace!("to_tls");
-
-        if let Some(mut remote) = client.tls_stream().await {
-            read_loop(server, &mut remote, server_data_func, remote_data_func).await;
-        }
+    //bytes in server buffer are kept until a remote is connected.
+    if let Some((target, mut remote)) = connect(route, peer, &head).await {
+        let (to, from) = Flow::backend_pair(route.backend(&target));
+        server_data_func.set_backend(Some(to));
+        remote_data_func.set_backend(Some(from));
+        read_loop(server, &mut remote, server_data_func, remote_data_func).await;
+        route.closed(&target);
+        route.done(&target).await;
     }
 }
 
@@ -165,22 +500,26 @@ impl<T> FuncStream for RouteTls<T>
 where
     T: FuncR,
 {
-    async fn consume(mut self, server: TcpStream) {
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("route tls start");
 
+        let head = proxy_header(&self.route, &server, peer, true).await;
         let mut server = if let Some(s) = tls_accept(&self.tls_acceptor, server).await {
             BufStream::new(s)
         } else {
-            return;
+            return (0, 0);
         };
 
         route(
             &mut server,
+            peer,
+            head,
             &mut self.route,
             &mut self.server_data_func,
             &mut self.remote_data_func,
         )
         .await;
+        (self.server_data_func.sum(), self.remote_data_func.sum())
     }
 }
 
@@ -218,18 +557,22 @@ impl<T> FuncStream for Route<T>
 where
     T: FuncR,
 {
-    async fn consume(mut self, server: TcpStream) {
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("route start");
 
+        let head = proxy_header(&self.route, &server, peer, false).await;
         let mut server = BufStream::new(server);
 
         route(
             &mut server,
+            peer,
+            head,
             &mut self.route,
             &mut self.server_data_func,
             &mut self.remote_data_func,
         )
         .await;
+        (self.server_data_func.sum(), self.remote_data_func.sum())
     }
 }
 
@@ -246,6 +589,73 @@ where
     }
 }
 
+//targets of "update" sentence replace targets of "finder", new targets are checked.
+//"checks" of targets which are removed are aborted before a target can be added again.
+//it ends when server drops the sender, then its checks are aborted.
+fn updater(
+    r: &RouteInfo,
+    finder: &RouteFinder,
+    state: &mpsc::Sender<StateInfo>,
+    mut checks: HashMap<String, AbortHandle>,
+) -> mpsc::Sender<(Vec<String>, Vec<usize>)> {
+    let (sender, mut receiver) = mpsc::channel::<(Vec<String>, Vec<usize>)>(UPDATE_CAPACITY);
+    let (check, protoc) = (r.check.clone(), r.remote_protoc);
+    let (finder, state) = (finder.clone(), state.clone());
+    tokio::spawn(async move {
+        while let Some((addrs, proportion)) = receiver.recv().await {
+            info!("update[{}]: {}", finder.listener, addrs.join(","));
+            let removed: Vec<String> = checks
+                .keys()
+                .filter(|t| !addrs.contains(t))
+                .cloned()
+                .collect();
+            let added = finder.update(addrs, proportion).await;
+            for t in removed {
+                if let Some(h) = checks.remove(&t) {
+                    h.abort();
+                }
+                debug!("health check[{t}] end");
+                let _ = state.send(StateInfo::Removed(t)).await;
+            }
+            checks.extend(check.start(protoc, &added, &finder, &state));
+        }
+        checks.values().for_each(|h| h.abort());
+    });
+    sender
+}
+
+//like "server_accept", health check of targets reports to server state.
+//pools and func are made by "f" after listener is bound.
+async fn route_accept<F: FuncStream>(
+    mut r: RouteInfo,
+    f: impl FnOnce(&RouteInfo) -> (PoolFinder, F),
+) {
+    if let Some((mut server, a, b)) = Server::new(&r.server_addr).await {
+        r.listener = server.addr().to_string();
+        let (pools, func) = f(&r);
+        let meter = listener_meter(&r.listener);
+        hold(*server.addr(), r.to_string(), meter.clone(), a, b).await;
+        server.set_accept_proxy(r.accept_proxy.clone());
+        server.set_meter(Some(meter));
+        let state = server.state_sender().clone();
+        let checks = r
+            .check
+            .start(r.remote_protoc, &r.remote_addrs, pools.default(), &state);
+        server.set_update(Some(updater(&r, pools.default(), &state, checks)));
+        //pools aren't updated, their checks end with server.
+        let mut pool_checks = Vec::new();
+        for (addrs, finder) in pools.pools() {
+            pool_checks.extend(
+                r.check
+                    .start(r.remote_protoc, addrs, finder, &state)
+                    .into_values(),
+            );
+        }
+        server.accept(func).await;
+        pool_checks.iter().for_each(|h| h.abort());
+    }
+}
+
 pub(crate) fn start_up(r: RouteInfo) {
     debug!("start_up {:?}", r);
     new_thread_tokiort_block_on(async move { server(r).await });
@@ -258,27 +668,60 @@ async fn server(r: RouteInfo) {
         Protoc::HTTPPT => http::http_pt(r).await,
         Protoc::TCP => {
             debug!("server tcp start up");
-            let ra = RouteAlg(r.remote_addrs, 0, get_index(r.proportion));
-            let o = Route::new(
-                RouteFinder::new(ra, r.remote_protoc),
-                RouteR::new(),
-                RouteR::new(),
-            );
-            server_accept(&r.server_addr, o).await;
+            route_accept(r, |r| {
+                let pools = PoolFinder::new(r);
+                let (f1, f2) = RouteR::pair(&r.listener);
+                let o = Route::new(pools.default().clone(), f1, f2);
+                (pools, o)
+            })
+            .await;
         }
         Protoc::TLS => {
             debug!("server tls start up");
             if let Some(t) = get_tls_acceptor().await {
-                let ra = RouteAlg(r.remote_addrs, 0, get_index(r.proportion));
-                let o = RouteTls::new(
-                    RouteFinder::new(ra, r.remote_protoc),
-                    RouteR::new(),
-                    RouteR::new(),
-                    t,
-                );
-                server_accept(&r.server_addr, o).await;
+                route_accept(r, |r| {
+                    let pools = PoolFinder::new(r);
+                    let (f1, f2) = RouteR::pair(&r.listener);
+                    let o = RouteTls::new(pools.default().clone(), f1, f2, t);
+                    (pools, o)
+                })
+                .await;
             }
         }
-        Protoc::UDP => {}
+        Protoc::UDP => udp::udp(r).await,
+        Protoc::SOCKS5 => error!("socks5 server has no route target"),
+        Protoc::SNI => sni::sni(r).await,
     }
 }
+
+#[test]
+fn test() {
+    let live = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let dead = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addrs = vec![
+        live.local_addr().unwrap().to_string(),
+        dead.local_addr().unwrap().to_string(),
+    ];
+    drop(dead);
+    let server = "127.0.0.1:3".to_string();
+    let mut r = RouteInfo::new(Protoc::TCP, server, Protoc::TCP, addrs.clone(), vec![1, 1]);
+    assert!(r.set_option("balance", "iphash") && r.set_option(ATTEMPTS, "2"));
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        //every peer gets the live target, even if its hash chooses the dead one.
+        let mut finder = r.balance.finder(&r, addrs.clone(), vec![1, 1]);
+        for n in 1..10 {
+            let peer = SocketAddr::from(([10, 0, 0, n], 1000));
+            let o = connect(&mut finder, peer, &[]).await.map(|o| o.0);
+            assert_eq!(o.as_ref(), Some(&addrs[0]));
+        }
+        //attempts stop when every target is tried.
+        let mut finder = r.balance.finder(&r, addrs[1..].to_vec(), vec![1]);
+        let peer = SocketAddr::from(([10, 0, 0, 1], 1000));
+        assert!(connect(&mut finder, peer, &[]).await.is_none());
+    });
+    remove_meters(&"127.0.0.1:3".parse().unwrap());
+}
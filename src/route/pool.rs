@@ -0,0 +1,291 @@
+use super::*;
+use regex::Regex;
+
+pub(super) const HOST: &str = "host";
+pub(super) const ALPN: &str = "alpn";
+pub(super) const PATH: &str = "path";
+pub(super) const REGEX: &str = "regex";
+
+const METHOD: &str = "method";
+const STRIP: &str = "strip";
+const REWRITE: &str = "rewrite";
+
+///how path of request is changed by a path or regex pool.
+#[derive(Clone, Debug)]
+enum Rewrite {
+    Strip,
+    Replace(String),
+}
+
+///a pool of targets for some host, ALPN protocol, path prefix or regex, like "a.com>127.0.0.1:20000,127.0.0.1:20001>1:1".
+///host is exact or wildcard like "*.a.com", which matches any subdomain of "a.com".
+///"method", "strip" and "rewrite" follow a path or regex pool.
+#[derive(Clone, Debug)]
+pub(super) struct Pool {
+    kind: &'static str,
+    pattern: String,
+    regex: Option<Regex>,
+    methods: Vec<String>,
+    rewrite: Option<Rewrite>,
+    addrs: Vec<String>,
+    proportion: Vec<usize>,
+}
+
+impl fmt::Display for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p: Vec<String> = self.proportion.iter().map(|n| n.to_string()).collect();
+        write!(
+            f,
+            " {}={}>{}>{}",
+            self.kind,
+            self.pattern,
+            self.addrs.join(","),
+            p.join(":")
+        )?;
+        if !self.methods.is_empty() {
+            write!(f, " {METHOD}={}", self.methods.join(","))?;
+        }
+        match &self.rewrite {
+            Some(Rewrite::Strip) => write!(f, " {STRIP}=yes"),
+            Some(Rewrite::Replace(s)) => write!(f, " {REWRITE}={s}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Pool {
+    ///"v" is "pattern>targets>proportion", proportion can be omitted.
+    pub(super) fn new(kind: &'static str, v: &str) -> Option<Self> {
+        //pattern may have '>', proportion has only digits and ':'.
+        let is_p = v
+            .rsplit('>')
+            .next()
+            .is_some_and(|s| s.bytes().all(|b| b.is_ascii_digit() || b == b':'));
+        let mut iter = v.rsplitn(if is_p { 3 } else { 2 }, '>');
+        let p = if is_p { iter.next() } else { None };
+        let addrs = iter.next().filter(|s| !s.is_empty())?;
+        let pattern = iter.next().filter(|s| !s.is_empty())?;
+
+        let mut regex = None;
+        let pattern = match kind {
+            HOST => {
+                let s = host_name(pattern);
+                if s.trim_start_matches("*.").contains('*') {
+                    return None;
+                }
+                s
+            }
+            PATH if !pattern.starts_with('/') => return None,
+            REGEX => {
+                regex = Some(
+                    Regex::new(pattern)
+                        .map_err(|e| error!("regex pool: {e}"))
+                        .ok()?,
+                );
+                pattern.to_string()
+            }
+            _ => pattern.to_string(),
+        };
+        let (addrs, mut proportion) = some_addr_proportion(addrs, p);
+        if duplicate(&addrs).is_some() {
+            return None;
+        }
+        divide(&mut proportion);
+        Some(Self {
+            kind,
+            pattern,
+            regex,
+            methods: Vec::new(),
+            rewrite: None,
+            addrs,
+            proportion,
+        })
+    }
+
+    pub(super) fn is_modifier(k: &str) -> bool {
+        [METHOD, STRIP, REWRITE].contains(&k)
+    }
+
+    ///set a modifier of path or regex pool.
+    pub(super) fn set(&mut self, k: &str, v: &str) -> bool {
+        if self.kind != PATH && self.kind != REGEX {
+            return false;
+        }
+        match k {
+            METHOD => {
+                self.methods = v.split(',').map(|s| s.to_ascii_uppercase()).collect();
+                !v.is_empty()
+            }
+            STRIP if self.kind == PATH && v == "yes" => {
+                self.rewrite = Some(Rewrite::Strip);
+                true
+            }
+            REWRITE => {
+                self.rewrite = Some(Rewrite::Replace(v.to_string()));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    //a wildcard matches a longer host name than its suffix.
+    fn matches(&self, host: &str) -> bool {
+        if let Some(s) = self.pattern.strip_prefix('*') {
+            host.len() > s.len() && host.ends_with(s)
+        } else {
+            host == self.pattern
+        }
+    }
+
+    //path prefix ends at a segment, "/api" matches "/api", "/api/a" and "/api?a", but not "/apiary".
+    fn is_prefix(&self, path: &str) -> bool {
+        path.strip_prefix(self.pattern.as_str())
+            .is_some_and(|rest| {
+                self.pattern.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?'])
+            })
+    }
+
+    fn accept(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m == method)
+    }
+
+    //path which is sent to target, none if it isn't changed.
+    fn rewrite(&self, path: &str) -> Option<String> {
+        let r = self.rewrite.as_ref()?;
+        if let Some(re) = &self.regex {
+            let s = match r {
+                Rewrite::Replace(s) => s,
+                Rewrite::Strip => return None,
+            };
+            return Some(re.replace(path, s.as_str()).into_owned());
+        }
+        let rest = &path[self.pattern.len()..];
+        let s = match r {
+            Rewrite::Strip => rest.to_string(),
+            Rewrite::Replace(s) => format!("{}{rest}", s.trim_end_matches('/')),
+        };
+        if s.starts_with('/') {
+            Some(s)
+        } else {
+            Some(format!("/{s}"))
+        }
+    }
+}
+
+//host name in lowercase without the trailing dot.
+fn host_name(s: &str) -> String {
+    s.trim_end_matches('.').to_ascii_lowercase()
+}
+
+///route finders of pools, the finder of route targets is default.
+#[derive(Clone)]
+pub(super) struct PoolFinder {
+    pools: Arc<Vec<(Pool, RouteFinder)>>,
+    default: RouteFinder,
+}
+
+impl PoolFinder {
+    pub(super) fn new(r: &RouteInfo) -> Self {
+        let pools = r
+            .pools
+            .iter()
+            .map(|p| {
+                let f = r.balance.finder(r, p.addrs.clone(), p.proportion.clone());
+                (p.clone(), f)
+            })
+            .collect();
+        let default = r
+            .balance
+            .finder(r, r.remote_addrs.clone(), r.proportion.clone());
+        Self {
+            pools: Arc::new(pools),
+            default,
+        }
+    }
+
+    pub(super) fn default(&self) -> &RouteFinder {
+        &self.default
+    }
+
+    ///targets and finder of every pool except default.
+    pub(super) fn pools(&self) -> impl Iterator<Item = (&Vec<String>, &RouteFinder)> {
+        self.pools.iter().map(|(p, f)| (&p.addrs, f))
+    }
+
+    ///a pool of ALPN protocol which client offers is the first choice.
+    ///then exact host, then the longest wildcard host, then default.
+    pub(super) fn get(&self, host: Option<&str>, alpn: &[String]) -> RouteFinder {
+        self.find(host, alpn)
+            .map_or(&self.default, |o| &o.1)
+            .clone()
+    }
+
+    fn find(&self, host: Option<&str>, alpn: &[String]) -> Option<&(Pool, RouteFinder)> {
+        let pools = self.pools.iter();
+        if let Some(o) = pools
+            .clone()
+            .find(|(p, _)| p.kind == ALPN && alpn.contains(&p.pattern))
+        {
+            trace!("pool alpn: {}", o.0.pattern);
+            return Some(o);
+        }
+        let host = host_name(host?);
+        let o = pools
+            .filter(|(p, _)| p.kind == HOST && p.matches(&host))
+            .max_by_key(|(p, _)| (!p.pattern.starts_with('*'), p.pattern.len()))?;
+        trace!("pool host: {}", o.0.pattern);
+        Some(o)
+    }
+
+    ///pool of request, the longest path prefix first, then regexes in order, then host.
+    ///key of pool is returned, and path if it's rewritten.
+    pub(super) fn request(
+        &self,
+        host: &str,
+        method: &str,
+        path: &str,
+    ) -> (String, RouteFinder, Option<String>) {
+        let pools = self.pools.iter().filter(|(p, _)| p.accept(method));
+        let o = pools
+            .clone()
+            .filter(|(p, _)| p.kind == PATH && p.is_prefix(path))
+            .max_by_key(|(p, _)| p.pattern.len())
+            .or_else(|| {
+                pools.clone().find(|(p, _)| {
+                    p.kind == REGEX && p.regex.as_ref().is_some_and(|re| re.is_match(path))
+                })
+            });
+        if let Some((p, _)) = o {
+            trace!("pool {}: {}", p.kind, p.pattern);
+        }
+        match o.or_else(|| self.find(Some(host), &[])) {
+            Some((p, f)) => (p.to_string(), f.clone(), p.rewrite(path)),
+            None => (String::new(), self.default.clone(), None),
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let p = Pool::new(HOST, "*.A.com.>127.0.0.1:1,127.0.0.1:2>2:4").unwrap();
+    assert_eq!(p.to_string(), " host=*.a.com>127.0.0.1:1,127.0.0.1:2>1:2");
+    assert!(p.matches("b.a.com"));
+    assert!(p.matches("c.b.a.com"));
+    assert!(!p.matches("a.com"));
+    assert!(Pool::new(HOST, "a.*.com>127.0.0.1:1").is_none());
+    assert!(Pool::new(ALPN, "h2").is_none());
+
+    let p = Pool::new(REGEX, "^/(?P<x>img)/>127.0.0.1:1").unwrap();
+    assert_eq!(p.pattern, "^/(?P<x>img)/");
+    let mut p = Pool::new(PATH, "/api>127.0.0.1:1>1").unwrap();
+    assert!(p.is_prefix("/api") && p.is_prefix("/api/a") && p.is_prefix("/api?a"));
+    assert!(!p.is_prefix("/apiary") && !p.is_prefix("/ap"));
+    assert!(Pool::new(PATH, "/api/>127.0.0.1:1").is_some_and(|o| o.is_prefix("/api/a")));
+    assert!(p.set(STRIP, "yes"));
+    assert_eq!(p.rewrite("/api/a?b").as_deref(), Some("/a?b"));
+    assert_eq!(p.rewrite("/api").as_deref(), Some("/"));
+    assert!(p.set(REWRITE, "/v2/"));
+    assert_eq!(p.rewrite("/api/a").as_deref(), Some("/v2/a"));
+    assert!(p.set(METHOD, "get"));
+    assert!(p.accept("GET") && !p.accept("POST"));
+}
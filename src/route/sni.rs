@@ -0,0 +1,65 @@
+use super::*;
+
+//TLS passthrough, a pool is chosen by ClientHello which is peeked, then bytes are routed untouched.
+#[derive(Clone)]
+struct RouteSni<T>
+where
+    T: FuncR,
+{
+    pools: PoolFinder,
+    server_data_func: T,
+    remote_data_func: T,
+}
+
+#[async_trait]
+impl<T> FuncStream for RouteSni<T>
+where
+    T: FuncR,
+{
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
+        trace!("route sni start");
+
+        let hello = if let Some(o) = ClientHello::peek(&server, SNI_WAIT).await {
+            o
+        } else {
+            debug!("route sni[{peer}]: no ClientHello");
+            return (0, 0);
+        };
+        debug!("route sni: {:?} {:?}", hello.sni(), hello.alpn());
+        let mut finder = self.pools.get(hello.sni().as_deref(), hello.alpn());
+        let head = proxy_header(&finder, &server, peer, false).await;
+        let mut server = BufStream::new(server);
+
+        route(
+            &mut server,
+            peer,
+            head,
+            &mut finder,
+            &mut self.server_data_func,
+            &mut self.remote_data_func,
+        )
+        .await;
+        (self.server_data_func.sum(), self.remote_data_func.sum())
+    }
+}
+
+pub(super) async fn sni(r: RouteInfo) {
+    debug!("server sni start up");
+
+    if r.remote_protoc != Protoc::SNI && r.remote_protoc != Protoc::TCP {
+        error!("sni server can only route to sni or tcp");
+        return;
+    }
+
+    route_accept(r, |r| {
+        let pools = PoolFinder::new(r);
+        let (f1, f2) = RouteR::pair(&r.listener);
+        let o = RouteSni {
+            pools: pools.clone(),
+            server_data_func: f1,
+            remote_data_func: f2,
+        };
+        (pools, o)
+    })
+    .await;
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[async_trait]
+impl FuncRemote for RouteFinder {
+    async fn remote(&mut self, peer: SocketAddr) -> Remote {
+        self.get(Some(peer), &[]).await
+    }
+
+    async fn done(&mut self, remote: &Remote) {
+        RouteFinder::done(self, remote.target()).await;
+    }
+}
+
+pub(super) async fn udp(mut r: RouteInfo) {
+    debug!("server udp start up");
+
+    if r.remote_protoc != Protoc::UDP {
+        error!("udp server can only route to udp");
+        return;
+    }
+
+    if let Some((mut server, a, b)) = UdpServer::new(&r.server_addr).await {
+        r.listener = server.addr().to_string();
+        let o = r
+            .balance
+            .finder(&r, r.remote_addrs.clone(), r.proportion.clone());
+        let meter = listener_meter(&r.listener);
+        hold(*server.addr(), r.to_string(), meter.clone(), a, b).await;
+        server.set_meter(Some(meter));
+        //udp targets aren't checked.
+        server.set_update(Some(updater(&r, &o, server.state_sender(), HashMap::new())));
+        server.relay(o).await;
+    }
+}
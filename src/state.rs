@@ -1,7 +1,11 @@
 use crate::core::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
 use std::net::{IpAddr, SocketAddr};
-use tokio::sync::{OnceCell, RwLock, mpsc, oneshot};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{OnceCell, RwLock, mpsc};
+use tokio::time::{Duration, interval};
 
 static SERVER_STATE: OnceCell<RwLock<HashMap<SocketAddr, ServerState>>> = OnceCell::const_new();
 
@@ -11,30 +15,107 @@ async fn server_state() -> &'static RwLock<HashMap<SocketAddr, ServerState>> {
         .await
 }
 
+///source of certificate and its sentence, no sentence if password is in clear text.
+type Certificates = Vec<(String, Option<String>)>;
+
+static CERTIFICATE: OnceCell<RwLock<Certificates>> = OnceCell::const_new();
+
+async fn certificate() -> &'static RwLock<Certificates> {
+    CERTIFICATE
+        .get_or_init(|| async { RwLock::new(Vec::new()) })
+        .await
+}
+
+static SAVE_PATH: OnceLock<String> = OnceLock::new();
+
+static AUTOSAVE: AtomicBool = AtomicBool::new(false);
+
 #[derive(CopyGetters, Getters, MutGetters, Setters)]
 pub(crate) struct ServerState {
     server: SocketAddr,
-    c: oneshot::Sender<ControlInfo>,
+    c: mpsc::Sender<ControlInfo>,
+    #[getset(get = "pub(crate)")]
+    sentence: String,
     #[getset(get_copy = "pub(crate)", set = "pub(crate)")]
     velocity: u32,
     #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
     date_time: String,
     ip_scope: HashSet<IpAddr>,
+    health: BTreeMap<String, bool>,
+    ///bytes from clients and to clients of connections which end.
+    #[getset(get_copy = "pub(crate)")]
+    bytes: (u64, u64),
+    ///bytes in the last second.
+    #[getset(get_copy = "pub(crate)")]
+    throughput: (u64, u64),
 }
 
 impl ServerState {
-    pub(crate) fn new(server: SocketAddr, c: oneshot::Sender<ControlInfo>) -> Self {
+    pub(crate) fn new(server: SocketAddr, c: mpsc::Sender<ControlInfo>) -> Self {
         Self {
             server,
             c,
+            sentence: String::new(),
             velocity: 0,
             date_time: String::new(),
             ip_scope: HashSet::new(),
+            health: BTreeMap::new(),
+            bytes: (0, 0),
+            throughput: (0, 0),
         }
     }
 
-    pub(crate) fn send(self, o: ControlInfo) -> Result<(), ControlInfo> {
-        self.c.send(o)
+    pub(crate) fn send(&self, o: ControlInfo) -> Result<(), ControlInfo> {
+        self.c.try_send(o).map_err(|e| e.into_inner())
+    }
+
+    //like "127.0.0.1:10000,velocity:3,up:1024,down:4096,up/s:10,down/s:40,127.0.0.1:20000=up [date_time]"
+    fn push_to(&self, s: &mut String) {
+        s.push_str(&self.server.to_string());
+        s.push_str(",velocity:");
+        s.push_str(&self.velocity.to_string());
+        s.push_str(&format!(",up:{},down:{}", self.bytes.0, self.bytes.1));
+        s.push_str(&format!(
+            ",up/s:{},down/s:{}",
+            self.throughput.0, self.throughput.1
+        ));
+        for (target, up) in &self.health {
+            s.push(',');
+            s.push_str(target);
+            s.push_str(if *up { "=up" } else { "=down" });
+        }
+        if self.date_time.len() > 0 {
+            s.push(' ');
+            s.push('[');
+            s.push_str(&self.date_time);
+            s.push(']');
+        }
+    }
+
+    //like '{"server":"127.0.0.1:10000","velocity":3,...,"health":{"127.0.0.1:20000":"up"},"date_time":null}'
+    fn push_json(&self, s: &mut String) {
+        let health: Vec<String> = self
+            .health
+            .iter()
+            .map(|(t, up)| format!("{}:\"{}\"", json_str(t), if *up { "up" } else { "down" }))
+            .collect();
+        let date_time = if self.date_time.is_empty() {
+            "null".to_string()
+        } else {
+            json_str(&self.date_time)
+        };
+        s.push_str(&format!(
+            "{{\"server\":\"{}\",\"sentence\":{},\"velocity\":{},\"up\":{},\"down\":{},\"up_per_second\":{},\"down_per_second\":{},\"health\":{{{}}},\"date_time\":{}}}",
+            self.server,
+            json_str(&self.sentence),
+            self.velocity,
+            self.bytes.0,
+            self.bytes.1,
+            self.throughput.0,
+            self.throughput.1,
+            health.join(","),
+            date_time
+        ));
     }
 }
 
@@ -50,15 +131,38 @@ async fn remove_state(server: &SocketAddr) -> Option<ServerState> {
     server_state().await.write().await.remove(server)
 }
 
+///state of "server" until it's shut down, throughput is sampled from "meter" of listener every second.
 pub(crate) async fn hold(
     server: SocketAddr,
-    c: oneshot::Sender<ControlInfo>,
+    sentence: String,
+    meter: Arc<Meter>,
+    c: mpsc::Sender<ControlInfo>,
     mut s: mpsc::Receiver<StateInfo>,
 ) {
-    add_state(ServerState::new(server.clone(), c)).await;
+    let mut ss = ServerState::new(server.clone(), c);
+    ss.sentence = sentence;
+    add_state(ss).await;
+    autosave().await;
 
     tokio::spawn(async move {
-        while let Some(o) = s.recv().await {
+        //bytes at the last second and throughput of it.
+        let (mut last, mut second) = ((0, 0), (0, 0));
+        let mut interval = interval(Duration::from_secs(1));
+        loop {
+            let o = tokio::select! {
+                o = s.recv() => if let Some(o) = o { o } else { break },
+                _ = interval.tick() => {
+                    let now = meter.bytes();
+                    let throughput = (now.0.saturating_sub(last.0), now.1.saturating_sub(last.1));
+                    if throughput != second {
+                        if let Some(ss) = server_state().await.write().await.get_mut(&server) {
+                            ss.throughput = throughput;
+                        }
+                    }
+                    (last, second) = (now, throughput);
+                    continue;
+                }
+            };
             match o {
                 StateInfo::Sum(velocity, date_time) => {
                     if let Some(ss) = server_state().await.write().await.get_mut(&server) {
@@ -66,15 +170,39 @@ pub(crate) async fn hold(
                         ss.date_time = date_time;
                     }
                 }
+                StateInfo::Health(target, up) => {
+                    if let Some(ss) = server_state().await.write().await.get_mut(&server) {
+                        ss.health.insert(target, up);
+                    }
+                }
+                StateInfo::Removed(target) => {
+                    if let Some(ss) = server_state().await.write().await.get_mut(&server) {
+                        ss.health.remove(&target);
+                    }
+                }
+                StateInfo::Bytes(up, down) => {
+                    if let Some(ss) = server_state().await.write().await.get_mut(&server) {
+                        ss.bytes = (ss.bytes.0 + up as u64, ss.bytes.1 + down as u64);
+                    }
+                }
             }
         }
     });
 }
 
-pub(crate) async fn server_accept(addr: &str, func: impl FuncStream) {
+///func is made by "f" with meter of listener after it's bound.
+pub(crate) async fn server_accept<F: FuncStream>(
+    addr: &str,
+    sentence: String,
+    accept_proxy: AcceptProxy,
+    f: impl FnOnce(Arc<Meter>) -> F,
+) {
     if let Some((mut server, a, b)) = Server::new(addr).await {
-        hold(*server.addr(), a, b).await;
-        server.accept(func).await;
+        let meter = listener_meter(&server.addr().to_string());
+        hold(*server.addr(), sentence, meter.clone(), a, b).await;
+        server.set_accept_proxy(accept_proxy);
+        server.set_meter(Some(meter.clone()));
+        server.accept(f(meter)).await;
     }
 }
 
@@ -82,16 +210,8 @@ pub(crate) async fn list() -> String {
     let map = server_state().await.read().await;
     let mut s = String::new();
     s.push_str("ok ");
-    for (key, val) in map.iter() {
-        s.push_str(&key.to_string());
-        s.push_str(",velocity:");
-        s.push_str(&val.velocity.to_string());
-        if val.date_time.len() > 0 {
-            s.push(' ');
-            s.push('[');
-            s.push_str(&val.date_time);
-            s.push(']');
-        }
+    for val in map.values() {
+        val.push_to(&mut s);
         s.push(' ');
     }
     s.pop();
@@ -103,22 +223,67 @@ pub(crate) async fn state_string(server: &SocketAddr) -> String {
     let mut s = String::new();
     s.push_str("ok ");
     if let Some(ss) = map.get(server) {
-        s.push_str(&ss.server.to_string());
-        s.push_str(",velocity:");
-        s.push_str(&ss.velocity.to_string());
-        if ss.date_time.len() > 0 {
-            s.push(' ');
-            s.push('[');
-            s.push_str(&ss.date_time);
-            s.push(']');
-        }
+        ss.push_to(&mut s);
     }
     s
 }
 
+///JSON array of all states.
+pub(crate) async fn list_json() -> String {
+    let map = server_state().await.read().await;
+    let mut s = String::new();
+    s.push('[');
+    for val in map.values() {
+        val.push_json(&mut s);
+        s.push(',');
+    }
+    if s.len() > 1 {
+        s.pop();
+    }
+    s.push(']');
+    s
+}
+
+///JSON of state, "null" if there is no server.
+pub(crate) async fn state_json(server: &SocketAddr) -> String {
+    let map = server_state().await.read().await;
+    let mut s = String::new();
+    if let Some(ss) = map.get(server) {
+        ss.push_json(&mut s);
+    } else {
+        s.push_str("null");
+    }
+    s
+}
+
+///sentence of server, none if there is no server.
+pub(crate) async fn sentence(server: &SocketAddr) -> Option<String> {
+    let map = server_state().await.read().await;
+    map.get(server).map(|ss| ss.sentence.clone())
+}
+
+///send targets to server, "sentence" is the sentence with them.
+pub(crate) async fn update(server: &SocketAddr, sentence: String, o: ControlInfo) -> bool {
+    let mut map = server_state().await.write().await;
+    let ss = if let Some(o) = map.get_mut(server) {
+        o
+    } else {
+        return false;
+    };
+    if ss.send(o).is_err() {
+        error!("update error");
+        return false;
+    }
+    ss.sentence = sentence;
+    drop(map);
+    autosave().await;
+    true
+}
+
 //find signal sender and send stop signal to server
 pub(crate) async fn shutdown(server: &SocketAddr) -> bool {
-    remove_state(server)
+    remove_meters(server);
+    let o = remove_state(server)
         .await
         .map(|s| {
             s.send(ControlInfo::Close)
@@ -126,5 +291,95 @@ pub(crate) async fn shutdown(server: &SocketAddr) -> bool {
                 .ok()
         })
         .flatten()
-        .is_some()
+        .is_some();
+    autosave().await;
+    o
+}
+
+///record a certificate sentence of "source" like "certificate f ./identity.p12" to save.
+///it's None if password isn't a reference, then nothing can be saved until it's replaced.
+///it replaces the sentence of the same "source".
+pub(crate) async fn add_certificate(source: &str, sentence: Option<String>) {
+    let mut v = certificate().await.write().await;
+    if let Some(o) = v.iter_mut().find(|o| o.0 == source) {
+        o.1 = sentence;
+    } else {
+        v.push((source.to_string(), sentence));
+    }
+    drop(v);
+    autosave().await;
+}
+
+///source of a certificate which can't be saved since its password is in clear text.
+pub(crate) async fn unsaved_certificate() -> Option<String> {
+    certificate()
+        .await
+        .read()
+        .await
+        .iter()
+        .find(|o| o.1.is_none())
+        .map(|o| o.0.clone())
+}
+
+///"path" is used by save sentence without path, "auto" means to save after every change.
+pub(crate) fn set_save(path: Option<String>, auto: bool) {
+    if let Some(path) = path {
+        let _ = SAVE_PATH.set(path);
+    }
+    AUTOSAVE.store(auto, Ordering::Relaxed);
+}
+
+async fn autosave() {
+    if AUTOSAVE.load(Ordering::Relaxed) {
+        save(None).await;
+    }
+}
+
+///write certificate sentences and server sentences to file, it can be loaded at startup.
+pub(crate) async fn save(path: Option<&str>) -> bool {
+    let path = if let Some(p) = path.or_else(|| SAVE_PATH.get().map(|p| p.as_str())) {
+        p
+    } else {
+        error!("save: no path");
+        return false;
+    };
+    if let Some(o) = unsaved_certificate().await {
+        error!("save: password of \"{o}\" is in clear text, it isn't saved");
+        return false;
+    }
+
+    let mut s = String::new();
+    s.push_str("# netproxy ");
+    s.push_str(&now_str());
+    s.push('\n');
+    for o in certificate()
+        .await
+        .read()
+        .await
+        .iter()
+        .filter_map(|o| o.1.as_ref())
+    {
+        s.push_str(o);
+        s.push('\n');
+    }
+    let map = server_state().await.read().await;
+    let mut v: Vec<&String> = map
+        .values()
+        .map(|ss| &ss.sentence)
+        .filter(|o| !o.is_empty())
+        .collect();
+    v.sort();
+    for o in v {
+        s.push_str(o);
+        s.push('\n');
+    }
+    drop(map);
+
+    //write a temporary file and rename it, so a crash never leaves a partial file.
+    let tmp = format!("{path}.tmp");
+    fs::write(&tmp, s)
+        .and_then(|_| fs::rename(&tmp, path))
+        .inspect(|_| debug!("save[{path}]"))
+        .map_err(|e| error!("save[{path}]: {e}"))
+        .is_ok()
 }
@@ -1,16 +1,35 @@
+mod socks5;
+
 use crate::core::*;
 use crate::state::*;
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio_native_tls::TlsAcceptor;
 
+const CONNECT: &str = "CONNECT";
+
+const ESTABLISHED: &[u8] = b"HTTP/1.1 200 Connection Established\r\n\r\n";
+
+const BAD_GATEWAY: &[u8] = b"HTTP/1.1 502 Bad Gateway\r\n\r\n";
+
+///headers only for proxy, never sent to origin.
+const HOP_HEADERS: [&str; 2] = ["Proxy-Connection", "Proxy-Authorization"];
+
+//headers which frame the message or choose its target, they are never removed by "Connection".
+const KEPT_HEADERS: [&str; 4] = ["connection", "content-length", "transfer-encoding", "host"];
+
+///protocol of remote, and meter of listener.
 #[derive(Clone, Debug)]
-struct VisitFinder(Protoc);
+struct VisitFinder(Protoc, Arc<Meter>);
 
 impl VisitFinder {
-    async fn get(&mut self, buf: &mut Vec<u8>) -> Option<Remote> {
+    ///get remote from request, "true" means it's a tunnel by "CONNECT host:port".
+    async fn get(&mut self, buf: &mut Vec<u8>) -> Option<(Remote, bool)> {
         let mut req = HttpRequest::parse(buf)
             .inspect_err(|e| {
                 error!("http request:{:?}", e);
@@ -18,21 +37,40 @@ impl VisitFinder {
             })
             .ok()?;
 
-        let host = req.get_host();
+        if req.method() == CONNECT {
+            let target = req.path();
+            let host = if let Some((h, _)) = target.rsplit_once(':') {
+                h.to_string()
+            } else {
+                warn!("no port in authority: {target}");
+                return None;
+            };
+            return Some((Remote::new(Protoc::TCP, target, host), true));
+        }
+
+        //absolute uri is from a client of proxy, target is its authority.
+        let path = req.path();
+        let (host, origin) = if let Some((_, a, o)) = split_uri(&path) {
+            (a.to_string(), Some(o.to_string()))
+        } else {
+            (req.get_host(), None)
+        };
+        rewrite(buf, &host, origin);
+
         if self.0 == Protoc::HTTP {
             let target = if host.contains(':') {
                 host.to_string()
             } else {
                 format!("{}:443", host)
             };
-            Some(Remote::new(self.0, target, host))
+            Some((Remote::new(self.0, target, host), false))
         } else if self.0 == Protoc::HTTPPT {
             let target = if host.contains(':') {
                 host.to_string()
             } else {
                 format!("{}:80", host)
             };
-            Some(Remote::new(self.0, target, host))
+            Some((Remote::new(self.0, target, host), false))
         } else {
             warn!("no remote");
             None
@@ -40,11 +78,38 @@ impl VisitFinder {
     }
 }
 
-#[derive(Debug)]
+//request line uses origin form, and headers for proxy are removed.
+fn rewrite(buf: &mut Vec<u8>, host: &str, origin: Option<String>) {
+    if let Some((mut head, n)) = HttpHead::parse(buf) {
+        if let Some(o) = origin {
+            head.set_second(o);
+            head.set_header("Host", host);
+        }
+        for h in HOP_HEADERS {
+            head.remove_header(h);
+        }
+        //headers listed in "Connection" are for this hop only, RFC 9110 7.6.1.
+        for h in head.connection_options() {
+            if !KEPT_HEADERS.contains(&h.as_str()) {
+                head.remove_header(&h);
+            }
+        }
+        let mut v = head.to_bytes();
+        v.extend_from_slice(&buf[n..]);
+        *buf = v;
+    }
+}
+
+const AUTH: &str = "auth";
+
+#[derive(Debug, Getters)]
 pub(crate) struct VisitInfo {
     server_protoc: Protoc,
+    #[getset(get = "pub(crate)")]
     server_addr: String,
     remote_protoc: Protoc,
+    auth: Option<String>,
+    accept_proxy: AcceptProxy,
 }
 
 impl VisitInfo {
@@ -53,70 +118,162 @@ impl VisitInfo {
             server_protoc,
             server_addr,
             remote_protoc,
+            auth: None,
+            accept_proxy: AcceptProxy::default(),
+        }
+    }
+
+    ///set an option like "key=value", return false if it's invalid.
+    pub(crate) fn set_option(&mut self, k: &str, v: &str) -> bool {
+        match k {
+            AUTH if self.server_protoc == Protoc::SOCKS5 => {
+                self.auth = Some(v.to_string());
+                true
+            }
+            ACCEPT_PROXY => self.accept_proxy.set(v),
+            _ => false,
         }
     }
 }
 
-#[derive(Clone, Debug, CopyGetters)]
+impl fmt::Display for VisitInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.server_protoc, self.remote_protoc, self.server_addr
+        )?;
+        if let Some(o) = &self.auth {
+            write!(f, " {AUTH}={o}")?;
+        }
+        self.accept_proxy.fmt(f)
+    }
+}
+
+///bytes are counted, and added to meter of listener.
+#[derive(Clone, Debug)]
 pub(crate) struct VisitR {
-    #[getset(get_copy = "pub(crate)")]
     sum: usize,
+    flow: Flow,
 }
 
 #[async_trait]
 impl FuncR for VisitR {
     async fn data(&mut self, buf: &mut Vec<u8>) {
         self.sum += buf.len();
+        self.flow.add(buf.len());
     }
 
     async fn enddata(&mut self, buf: &mut Vec<u8>) {
         self.sum += buf.len();
+        self.flow.add(buf.len());
+    }
+
+    fn sum(&self) -> usize {
+        self.sum
     }
 }
 
 impl VisitR {
-    fn new() -> Self {
-        Self { sum: 0 }
+    ///func of data from clients, and func of data to clients.
+    fn pair(m: Arc<Meter>) -> (Self, Self) {
+        let (a, b) = Flow::pair(m);
+        (Self { sum: 0, flow: a }, Self { sum: 0, flow: b })
     }
 }
 
 #[inline]
 async fn visit<T>(
     server: &mut BufStream<T>,
+    peer: SocketAddr,
+    tls: bool,
     v: &mut VisitFinder,
     server_data_func: &mut impl FuncR,
     remote_data_func: &mut impl FuncR,
 ) where
     T: AsyncReadExt + AsyncWriteExt + Unpin,
 {
-    server.read().await;
+    read_head(server).await;
     if server.is_empty() {
         return;
     }
 
-    let remote = if let Some(r) = v.get(server.r_buf_mut()).await {
-        r
-    } else {
+    let tunnel = HttpHead::parse(server.r_buf_mut()).is_some_and(|(h, _)| h.first() == CONNECT);
+    if tunnel {
+        if let Some((remote, _)) = v.get(server.r_buf_mut()).await {
+            tunnel_loop(server, remote, &v.1, server_data_func, remote_data_func).await;
+        }
         return;
+    }
+
+    let mut o = VisitHttp {
+        finder: v,
+        remote: None,
     };
+    http_loop(
+        server,
+        peer,
+        tls,
+        &mut o,
+        server_data_func,
+        remote_data_func,
+    )
+    .await;
+}
 
-    let remote_protoc = remote.protoc();
-    debug!("remote[{:?}]", remote.target());
+//every request on a connection goes to its own host, a tunnel is only by the first request.
+struct VisitHttp<'a> {
+    finder: &'a mut VisitFinder,
+    remote: Option<Remote>,
+}
 
-    let mut client = Client::new(remote);
+#[async_trait]
+impl FuncHttp for VisitHttp<'_> {
+    async fn choose(&mut self, buf: &mut Vec<u8>) -> Option<String> {
+        let (remote, tunnel) = self.finder.get(buf).await?;
+        if tunnel {
+            warn!("tunnel after request");
+            return None;
+        }
+        let key = remote.target().clone();
+        self.remote = Some(remote);
+        Some(key)
+    }
 
-    if remote_protoc == Protoc::TCP || remote_protoc == Protoc::HTTPPT {
-        trace!("to_tcp");
+    async fn connect(&mut self) -> Option<(String, Upstream)> {
+        let remote = self.remote.take()?;
+        let target = remote.target().clone();
+        debug!("remote[{:?}]", target);
+        let mut client = Client::new(remote);
+        measure(&[&self.finder.1], client.stream())
+            .await
+            .map(|s| (target, s))
+    }
 
-        if let Some(mut remote) = client.tcp_stream().await {
-            read_loop(server, &mut remote, server_data_func, remote_data_func).await;
-        }
-    } else {
-        trace!("to_tls");
+    async fn done(&mut self, _: &str) {}
+}
 
-        if let Some(mut remote) = client.tls_stream().await {
-            read_loop(server, &mut remote, server_data_func, remote_data_func).await;
-        }
+//reply to "CONNECT" when authority is connected, then transfer data in both directions.
+async fn tunnel_loop<T>(
+    server: &mut BufStream<T>,
+    remote: Remote,
+    meter: &Meter,
+    server_data_func: &mut impl FuncR,
+    remote_data_func: &mut impl FuncR,
+) where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    debug!("tunnel[{:?}]", remote.target());
+    if let Some(n) = head_len(server.r_buf_mut()) {
+        server.r_buf_mut().drain(..n);
+    }
+
+    let mut client = Client::new(remote);
+    if let Some(mut remote) = measure(&[meter], client.tcp_stream()).await {
+        server.write(ESTABLISHED.to_vec()).await;
+        read_loop(server, &mut remote, server_data_func, remote_data_func).await;
+    } else {
+        server.write(BAD_GATEWAY.to_vec()).await;
     }
 }
 
@@ -136,22 +293,25 @@ impl<T> FuncStream for VisitTls<T>
 where
     T: FuncR,
 {
-    async fn consume(mut self, server: TcpStream) {
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("visit tls start");
 
         let mut server = if let Some(s) = tls_accept(&self.tls_acceptor, server).await {
             BufStream::new(s)
         } else {
-            return;
+            return (0, 0);
         };
 
         visit(
             &mut server,
+            peer,
+            true,
             &mut self.visit,
             &mut self.server_data_func,
             &mut self.remote_data_func,
         )
         .await;
+        (self.server_data_func.sum(), self.remote_data_func.sum())
     }
 }
 
@@ -189,18 +349,21 @@ impl<T> FuncStream for Visit<T>
 where
     T: FuncR,
 {
-    async fn consume(mut self, server: TcpStream) {
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
         trace!("visit start");
 
         let mut server = BufStream::new(server);
 
         visit(
             &mut server,
+            peer,
+            false,
             &mut self.visit,
             &mut self.server_data_func,
             &mut self.remote_data_func,
         )
         .await;
+        (self.server_data_func.sum(), self.remote_data_func.sum())
     }
 }
 
@@ -228,19 +391,46 @@ async fn server(v: VisitInfo) {
         Protoc::HTTP => {
             debug!("server tls start up");
             if let Some(t) = get_tls_acceptor().await {
-                let o = VisitTls::new(
-                    VisitFinder(v.remote_protoc),
-                    VisitR::new(),
-                    VisitR::new(),
-                    t,
-                );
-                server_accept(&v.server_addr, o).await;
+                let sentence = v.to_string();
+                let p = v.remote_protoc;
+                server_accept(&v.server_addr, sentence, v.accept_proxy, |m| {
+                    let (f1, f2) = VisitR::pair(m.clone());
+                    VisitTls::new(VisitFinder(p, m), f1, f2, t)
+                })
+                .await;
             }
         }
         Protoc::HTTPPT => {
             debug!("server tcp start up");
-            let o = Visit::new(VisitFinder(v.remote_protoc), VisitR::new(), VisitR::new());
-            server_accept(&v.server_addr, o).await;
+            let sentence = v.to_string();
+            let p = v.remote_protoc;
+            server_accept(&v.server_addr, sentence, v.accept_proxy, |m| {
+                let (f1, f2) = VisitR::pair(m.clone());
+                Visit::new(VisitFinder(p, m), f1, f2)
+            })
+            .await;
+        }
+        Protoc::SOCKS5 => {
+            debug!("server socks5 start up");
+            let users = if let Some(path) = &v.auth {
+                if let Some(o) = socks5::load_users(path) {
+                    o
+                } else {
+                    return;
+                }
+            } else {
+                HashMap::new()
+            };
+            //associations count towards velocity of server.
+            if let Some((mut server, a, b)) = Server::new(&v.server_addr).await {
+                let meter = listener_meter(&server.addr().to_string());
+                let (f1, f2) = VisitR::pair(meter.clone());
+                hold(*server.addr(), v.to_string(), meter.clone(), a, b).await;
+                server.set_accept_proxy(v.accept_proxy);
+                server.set_meter(Some(meter.clone()));
+                let o = socks5::Socks5::new(users, server.counter().clone(), meter, f1, f2);
+                server.accept(o).await;
+            }
         }
         _ => {}
     }
@@ -0,0 +1,453 @@
+use super::*;
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::net::{UdpSocket, lookup_host};
+
+const VERSION: u8 = 5;
+const AUTH_VERSION: u8 = 1;
+
+const NO_AUTH: u8 = 0;
+const USER_PASSWORD: u8 = 2;
+const NO_METHOD: u8 = 0xFF;
+
+const CMD_CONNECT: u8 = 1;
+const CMD_UDP_ASSOCIATE: u8 = 3;
+
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+const SUCCEEDED: u8 = 0;
+const GENERAL_FAILURE: u8 = 1;
+const COMMAND_NOT_SUPPORTED: u8 = 7;
+const ADDRESS_NOT_SUPPORTED: u8 = 8;
+
+const DATAGRAM_CAPACITY: usize = 65535;
+
+///"RSV FRAG" of UDP request header.
+const UDP_HEAD: usize = 3;
+
+//read until there are "n" bytes in buffer.
+async fn fill<T>(s: &mut BufStream<T>, n: usize) -> bool
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    while s.r_buf_mut().len() < n {
+        if s.r_f() {
+            return false;
+        }
+        s.read().await;
+    }
+    true
+}
+
+//take "n" bytes from buffer.
+async fn take<T>(s: &mut BufStream<T>, n: usize) -> Option<Vec<u8>>
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    if fill(s, n).await {
+        Some(s.r_buf_mut().drain(..n).collect())
+    } else {
+        None
+    }
+}
+
+///parse "ATYP DST.ADDR DST.PORT", return "host:port" and its length.
+pub(super) fn parse_addr(buf: &[u8]) -> Result<Option<(String, usize)>, u8> {
+    let (host, n) = match buf.first() {
+        Some(&ATYP_IPV4) => {
+            if buf.len() < 5 {
+                return Ok(None);
+            }
+            let a: [u8; 4] = buf[1..5].try_into().unwrap_or_default();
+            (Ipv4Addr::from(a).to_string(), 5)
+        }
+        Some(&ATYP_IPV6) => {
+            if buf.len() < 17 {
+                return Ok(None);
+            }
+            let a: [u8; 16] = buf[1..17].try_into().unwrap_or_default();
+            (format!("[{}]", Ipv6Addr::from(a)), 17)
+        }
+        Some(&ATYP_DOMAIN) => {
+            let len = if let Some(n) = buf.get(1) {
+                *n as usize
+            } else {
+                return Ok(None);
+            };
+            if buf.len() < 2 + len {
+                return Ok(None);
+            }
+            (into_str(&buf[2..2 + len]), 2 + len)
+        }
+        Some(_) => return Err(ADDRESS_NOT_SUPPORTED),
+        None => return Ok(None),
+    };
+    if buf.len() < n + 2 {
+        return Ok(None);
+    }
+    let port = u16::from_be_bytes([buf[n], buf[n + 1]]);
+    Ok(Some((format!("{host}:{port}"), n + 2)))
+}
+
+///encode "ATYP BND.ADDR BND.PORT".
+pub(super) fn encode_addr(addr: &SocketAddr) -> Vec<u8> {
+    let mut v = Vec::new();
+    match addr {
+        SocketAddr::V4(a) => {
+            v.push(ATYP_IPV4);
+            v.extend_from_slice(&a.ip().octets());
+        }
+        SocketAddr::V6(a) => {
+            v.push(ATYP_IPV6);
+            v.extend_from_slice(&a.ip().octets());
+        }
+    }
+    v.extend_from_slice(&addr.port().to_be_bytes());
+    v
+}
+
+async fn reply<T>(s: &mut BufStream<T>, rep: u8, addr: &SocketAddr)
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    let mut v = vec![VERSION, rep, 0];
+    v.extend(encode_addr(addr));
+    s.write(v).await;
+}
+
+///users from file, one "user:password" per line.
+pub(super) fn load_users(path: &str) -> Option<HashMap<String, String>> {
+    let s = fs::read_to_string(path)
+        .map_err(|e| error!("socks5 users[{path}]: {e}"))
+        .ok()?;
+    Some(
+        s.lines()
+            .filter_map(|l| l.trim().split_once(':'))
+            .map(|(u, p)| (u.to_string(), p.to_string()))
+            .collect(),
+    )
+}
+
+#[derive(Clone)]
+pub(super) struct Socks5<T>
+where
+    T: FuncR,
+{
+    users: Arc<HashMap<String, String>>,
+    counter: Arc<AtomicU32>,
+    meter: Arc<Meter>,
+    server_data_func: T,
+    remote_data_func: T,
+}
+
+#[async_trait]
+impl<T> FuncStream for Socks5<T>
+where
+    T: FuncR,
+{
+    async fn consume(mut self, server: TcpStream, peer: SocketAddr) -> (usize, usize) {
+        trace!("socks5 start");
+
+        let local = server
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        let mut server = BufStream::new(server);
+        if !self.handshake(&mut server).await {
+            return (0, 0);
+        }
+
+        let (cmd, target) = if let Some(o) = request(&mut server, &local).await {
+            o
+        } else {
+            return (0, 0);
+        };
+        match cmd {
+            CMD_CONNECT => self.connect(&mut server, target, &local).await,
+            CMD_UDP_ASSOCIATE => self.associate(&mut server, &local, peer.ip()).await,
+            _ => {
+                debug!("socks5 command not supported: {cmd}");
+                reply(&mut server, COMMAND_NOT_SUPPORTED, &local).await;
+            }
+        }
+        (self.server_data_func.sum(), self.remote_data_func.sum())
+    }
+}
+
+impl<T> Socks5<T>
+where
+    T: FuncR,
+{
+    pub(super) fn new(
+        users: HashMap<String, String>,
+        counter: Arc<AtomicU32>,
+        meter: Arc<Meter>,
+        server_data_func: T,
+        remote_data_func: T,
+    ) -> Self {
+        Self {
+            users: Arc::new(users),
+            counter,
+            meter,
+            server_data_func,
+            remote_data_func,
+        }
+    }
+
+    //choose a method, username/password is required if there are users.
+    async fn handshake<S>(&self, s: &mut BufStream<S>) -> bool
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        let head = if let Some(o) = take(s, 2).await {
+            o
+        } else {
+            return false;
+        };
+        if head[0] != VERSION {
+            debug!("socks version: {}", head[0]);
+            return false;
+        }
+        let methods = if let Some(o) = take(s, head[1] as usize).await {
+            o
+        } else {
+            return false;
+        };
+
+        let method = if self.users.is_empty() {
+            NO_AUTH
+        } else {
+            USER_PASSWORD
+        };
+        if !methods.contains(&method) {
+            s.write(vec![VERSION, NO_METHOD]).await;
+            return false;
+        }
+        s.write(vec![VERSION, method]).await;
+        if method == NO_AUTH {
+            return true;
+        }
+        self.authenticate(s).await
+    }
+
+    //RFC 1929, "VER ULEN UNAME PLEN PASSWD".
+    async fn authenticate<S>(&self, s: &mut BufStream<S>) -> bool
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        let user = if let Some(o) = take(s, 2).await {
+            if o[0] != AUTH_VERSION {
+                return false;
+            }
+            take(s, o[1] as usize).await
+        } else {
+            None
+        };
+        let pwd = if let Some(o) = take(s, 1).await {
+            take(s, o[0] as usize).await
+        } else {
+            None
+        };
+        let (user, pwd) = match (user, pwd) {
+            (Some(u), Some(p)) => (into_str(&u), into_str(&p)),
+            _ => return false,
+        };
+
+        if self.users.get(&user) == Some(&pwd) {
+            trace!("socks5 user: {user}");
+            s.write(vec![AUTH_VERSION, SUCCEEDED]).await;
+            true
+        } else {
+            info!("socks5 reject user: {user}");
+            s.write(vec![AUTH_VERSION, GENERAL_FAILURE]).await;
+            false
+        }
+    }
+
+    async fn connect<S>(&mut self, s: &mut BufStream<S>, target: String, local: &SocketAddr)
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        debug!("socks5 connect[{target}]");
+        let host = target.rsplit_once(':').map(|o| o.0).unwrap_or_default();
+        let mut client = Client::new(Remote::new(Protoc::TCP, target.clone(), host.to_string()));
+        if let Some(mut remote) = measure(&[&self.meter], client.tcp_stream()).await {
+            reply(s, SUCCEEDED, local).await;
+            read_loop(
+                s,
+                &mut remote,
+                &mut self.server_data_func,
+                &mut self.remote_data_func,
+            )
+            .await;
+        } else {
+            reply(s, GENERAL_FAILURE, local).await;
+        }
+    }
+}
+
+impl<T> Socks5<T>
+where
+    T: FuncR,
+{
+    //relay datagrams of client "peer" until the control connection closes.
+    async fn associate<S>(&mut self, s: &mut BufStream<S>, local: &SocketAddr, peer: IpAddr)
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        let socket = match UdpSocket::bind(SocketAddr::new(local.ip(), 0)).await {
+            Ok(o) => o,
+            Err(e) => {
+                error!("socks5 associate: {e}");
+                reply(s, GENERAL_FAILURE, local).await;
+                return;
+            }
+        };
+        let relay = socket.local_addr().unwrap_or(*local);
+        debug!("socks5 associate[{relay}]");
+        reply(s, SUCCEEDED, &relay).await;
+        self.counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut association = Association::new(socket, peer);
+        let mut buf = vec![0; DATAGRAM_CAPACITY];
+        loop {
+            tokio::select! {
+                _ = s.read() => {
+                    s.take_buf();
+                    if s.r_f() {
+                        debug!("socks5 associate[{relay}] end");
+                        return;
+                    }
+                }
+                r = association.socket.recv_from(&mut buf) => {
+                    match r {
+                        Ok((n, from)) => association.relay(&buf[..n], from).await,
+                        Err(e) => {
+                            error!("socks5 associate recv: {e}");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+//a UDP relay socket, datagrams from client are sent to targets, datagrams from targets are sent to client.
+//datagrams from other addresses are dropped.
+struct Association {
+    socket: UdpSocket,
+    peer: IpAddr,
+    client: Option<SocketAddr>,
+    targets: HashMap<String, SocketAddr>,
+}
+
+impl Association {
+    fn new(socket: UdpSocket, peer: IpAddr) -> Self {
+        Self {
+            socket,
+            peer,
+            client: None,
+            targets: HashMap::new(),
+        }
+    }
+
+    async fn relay(&mut self, buf: &[u8], from: SocketAddr) {
+        let is_client = match self.client {
+            Some(c) => c == from,
+            None => from.ip() == self.peer,
+        };
+        if is_client {
+            self.client = Some(from);
+            self.forward(buf).await;
+        } else if !self.targets.values().any(|a| *a == from) {
+            debug!("socks5 associate drop datagram from {from}");
+        } else if let Some(c) = self.client {
+            //"RSV FRAG ATYP DST.ADDR DST.PORT DATA"
+            let mut v = vec![0; UDP_HEAD];
+            v.extend(encode_addr(&from));
+            v.extend_from_slice(buf);
+            if let Err(e) = self.socket.send_to(&v, c).await {
+                error!("socks5 associate send_to: {e}");
+            }
+        }
+    }
+
+    //fragment is not supported, such datagram is dropped.
+    async fn forward(&mut self, buf: &[u8]) {
+        if buf.len() <= UDP_HEAD || buf[2] != 0 {
+            debug!("socks5 associate drop datagram");
+            return;
+        }
+        let (target, n) = match parse_addr(&buf[UDP_HEAD..]) {
+            Ok(Some(o)) => o,
+            _ => return,
+        };
+        let addr = if let Some(a) = self.targets.get(&target) {
+            *a
+        } else {
+            let a = match lookup_host(&target).await.map(|mut o| o.next()) {
+                Ok(Some(a)) => a,
+                _ => {
+                    debug!("socks5 associate resolve: {target}");
+                    return;
+                }
+            };
+            self.targets.insert(target, a);
+            a
+        };
+        if let Err(e) = self.socket.send_to(&buf[UDP_HEAD + n..], addr).await {
+            error!("socks5 associate send_to: {e}");
+        }
+    }
+}
+
+//"VER CMD RSV ATYP DST.ADDR DST.PORT", return command and "host:port".
+async fn request<T>(s: &mut BufStream<T>, local: &SocketAddr) -> Option<(u8, String)>
+where
+    T: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    let head = take(s, 3).await?;
+    if head[0] != VERSION {
+        return None;
+    }
+    loop {
+        match parse_addr(s.r_buf_mut()) {
+            Ok(Some((target, n))) => {
+                s.r_buf_mut().drain(..n);
+                return Some((head[1], target));
+            }
+            Ok(None) => {
+                if s.r_f() {
+                    return None;
+                }
+                s.read().await;
+            }
+            Err(rep) => {
+                reply(s, rep, local).await;
+                return None;
+            }
+        }
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        parse_addr(&[ATYP_IPV4, 127, 0, 0, 1, 0, 80]),
+        Ok(Some(("127.0.0.1:80".to_string(), 7)))
+    );
+    assert_eq!(
+        parse_addr(&[ATYP_DOMAIN, 3, b'a', b'.', b'b', 1, 187]),
+        Ok(Some(("a.b:443".to_string(), 7)))
+    );
+    assert_eq!(parse_addr(&[ATYP_DOMAIN, 3, b'a']), Ok(None));
+    assert_eq!(parse_addr(&[9]), Err(ADDRESS_NOT_SUPPORTED));
+
+    let a: SocketAddr = "[::1]:1080".parse().unwrap();
+    let v = encode_addr(&a);
+    assert_eq!(parse_addr(&v), Ok(Some(("[::1]:1080".to_string(), 19))));
+}